@@ -0,0 +1,39 @@
+use criterion::{black_box, criterion_group, criterion_main, Criterion, Throughput};
+use pomidor::lexer::Lexer;
+
+const SOURCE: &str = "let five = 5;
+let ten = 10;
+
+let add = fn(x, y) {
+    x + y;
+};
+
+let result = add(five, ten);
+!-/ *5;
+5 < 10 > 5;
+
+if (5 < 10) {
+    return true;
+} else {
+    return false;
+}
+
+10 == 10;
+10 != 9;
+let name = user?.name ?: \"anonymous\";
+let total = items |> sum ?? 0;
+";
+
+fn tokenize(c: &mut Criterion) {
+    let source = SOURCE.repeat(64);
+    let lexer = Lexer::new();
+    let mut group = c.benchmark_group("lexer");
+    group.throughput(Throughput::Bytes(source.len() as u64));
+    group.bench_function("tokenize", |b| {
+        b.iter(|| lexer.tokenize(black_box(&source)).count())
+    });
+    group.finish();
+}
+
+criterion_group!(benches, tokenize);
+criterion_main!(benches);
@@ -1,162 +1,1726 @@
-use crate::token::{Token, TokenPos, TokenType};
+use crate::token::Keyword::{False, Null, True};
+use crate::token::Literal::{Bin, Char, Float, Hex, Ident, Int, Oct, Str};
+use crate::token::Spec::{Rbrace, Rbracket, Rparen, Semicolon};
+use crate::token::{LiteralMatcher, SourceSpan, Token, TokenPos, TokenType, LOOK_BEHIND};
+use regex::Regex;
+use std::error::Error;
+use std::fmt;
 
-pub struct Lexer {
-    literal_matcher: Box<dyn Fn(&str, usize) -> Option<TokenPos>>,
+pub type DefaultMatcher = LiteralMatcher;
+pub type BuiltMatcher = Box<dyn Fn(&str, usize) -> Option<TokenPos> + Send + Sync>;
+
+#[derive(Debug, Clone, Copy, Default, Eq, PartialEq)]
+pub struct LexerConfig {
+    /// Insert a `Semicolon` at a line break that follows a token able to end a statement.
+    pub automatic_semicolons: bool,
+}
+
+#[derive(Clone)]
+pub struct Lexer<M = DefaultMatcher>
+where
+    M: Fn(&str, usize) -> Option<TokenPos>,
+{
+    literal_matcher: M,
+    config: LexerConfig,
 }
 
-pub struct LexerIterator<'l, 'i> {
+const _: () = {
+    const fn assert_send_sync<T: Send + Sync>() {}
+    assert_send_sync::<Lexer<DefaultMatcher>>();
+};
+
+pub struct LexerIterator<'l, 'i, M = DefaultMatcher>
+where
+    M: Fn(&str, usize) -> Option<TokenPos>,
+{
     pos: usize,
     input: &'i str,
     current_line: usize,
-    lexer: &'l Lexer,
+    current_col: usize,
+    source_id: u32,
+    peeked: Option<Token>,
+    insert_semicolon: bool,
+    eof_emitted: bool,
+    errors: Vec<LexError>,
+    lexer: &'l Lexer<M>,
+}
+
+/// Yields every token, keeping lexer errors in the stream as `TokenType::Illegal`.
+pub struct LossyIterator<'l, 'i, M = DefaultMatcher>
+where
+    M: Fn(&str, usize) -> Option<TokenPos>,
+{
+    inner: LexerIterator<'l, 'i, M>,
+}
+
+/// An input fragment that no token matches; `line` and `col` are 0-based.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub struct LexError {
+    pub byte_offset: usize,
+    pub line: usize,
+    pub col: usize,
+    pub ch: char,
+}
+
+/// Yields the tokens of the inner iterator followed by exactly one `Eof`.
+pub struct EofTerminated<I> {
+    inner: I,
+    last: Option<(usize, usize, SourceSpan)>,
+    done: bool,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct LexerBuilder {
+    keywords: Vec<(String, TokenType)>,
+    patterns: Vec<(String, TokenType)>,
+    config: LexerConfig,
+}
+
+/// An edit of the source: bytes `start..end` of the old source are replaced by `new_text`.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct TextChange {
+    pub start: usize,
+    pub end: usize,
+    pub new_text: String,
 }
 
-impl<'l, 'i> Lexer {
+impl Lexer {
     pub fn new() -> Lexer {
+        Lexer::with_config(LexerConfig::default())
+    }
+
+    pub fn with_config(config: LexerConfig) -> Lexer {
+        Lexer::with_matcher(TokenType::literal_token_matcher(), config)
+    }
+}
+
+impl<'l, 'i, M> Lexer<M>
+where
+    M: Fn(&str, usize) -> Option<TokenPos>,
+{
+    pub fn with_matcher(literal_matcher: M, config: LexerConfig) -> Lexer<M> {
         Lexer {
-            literal_matcher: TokenType::literal_token_matcher(),
+            literal_matcher,
+            config,
         }
     }
 
-    pub fn tokenize(&'l self, input: &'i str) -> LexerIterator<'l, 'i> {
+    pub fn tokenize(&'l self, input: &'i str) -> LexerIterator<'l, 'i, M> {
+        self.tokenize_from(input, 0, 0, 0)
+    }
+
+    pub fn tokenize_lossy(&'l self, input: &'i str) -> LossyIterator<'l, 'i, M> {
+        self.tokenize(input).into_lossy()
+    }
+
+    pub fn tokenize_all(&self, input: &str) -> Vec<Token> {
+        self.tokenize_lossy(input).collect()
+    }
+
+    pub fn tokenize_valid(&self, input: &str) -> Vec<Token> {
+        self.tokenize(input).filter_map(Result::ok).collect()
+    }
+
+    fn tokenize_from(
+        &'l self,
+        input: &'i str,
+        pos: usize,
+        line: usize,
+        col: usize,
+    ) -> LexerIterator<'l, 'i, M> {
         LexerIterator {
-            pos: 0,
+            pos,
             input,
-            current_line: 0,
+            current_line: line,
+            current_col: col,
+            source_id: 0,
+            peeked: None,
+            insert_semicolon: false,
+            eof_emitted: false,
+            errors: Vec::new(),
             lexer: self,
         }
     }
 
-    pub fn match_token(&self, input: &'i str, start: usize) -> Option<TokenPos> {
-        (self.literal_matcher)(input, start)
+    /// Re-lexes from one token before the change until a token lines up
+    /// with an old one again, then splices in the shifted rest of `old_tokens`.
+    pub fn tokenize_incremental(
+        &self,
+        old_tokens: &[Token],
+        old_source: &str,
+        new_source: &str,
+        change: TextChange,
+    ) -> Vec<Token> {
+        let new_end = change.start + change.new_text.len();
+        let delta = new_end as isize - change.end as isize;
+        debug_assert_eq!(old_source.len() as isize + delta, new_source.len() as isize);
+        let mut restart = old_tokens
+            .iter()
+            .position(|t| t.span.end >= change.start)
+            .unwrap_or(old_tokens.len())
+            .saturating_sub(1);
+        // An inserted `Semicolon` sits on the line break that caused it, which may be inside
+        // a block comment; lexing cannot resume from there.
+        while restart > 0 && is_virtual_semicolon(&old_tokens[restart], old_source) {
+            restart -= 1;
+        }
+        let (pos, line, col) = match old_tokens.get(restart) {
+            Some(t) if restart > 0 => (t.span.start, t.line, t.col),
+            _ => (0, 0, 0),
+        };
+
+        let source_id = old_tokens.first().map_or(0, |t| t.source_id());
+        let mut tokens = old_tokens[..restart].to_vec();
+        let mut iter = self.tokenize_from(new_source, pos, line, col);
+        iter.insert_semicolon = restart > 0 && self.ends_statement(&old_tokens[restart - 1]);
+        for token in iter.with_source_id(source_id).into_lossy() {
+            // Likewise, what follows an inserted `Semicolon` may have been lexed in a
+            // different comment state, so it is never a safe point to resync. Neither is a
+            // token close behind the change: a `_` lexes differently after a digit, and so
+            // do digits after `0b`, so the bytes in front of the token must be old text too.
+            if token.span.start >= new_end + LOOK_BEHIND
+                && !is_virtual_semicolon(&token, new_source)
+            {
+                let old_start = (token.span.start as isize - delta) as usize;
+                let synced = old_tokens[restart..]
+                    .binary_search_by_key(&old_start, |t| t.span.start)
+                    .map(|i| restart + i);
+                if let Ok(i) = synced {
+                    let sync_line = old_tokens[i].line;
+                    let line_delta = token.line as isize - sync_line as isize;
+                    let col_delta = token.col as isize - old_tokens[i].col as isize;
+                    tokens.extend(old_tokens[i..].iter().map(|t| Token {
+                        line: (t.line as isize + line_delta) as usize,
+                        col: if t.line == sync_line {
+                            (t.col as isize + col_delta) as usize
+                        } else {
+                            t.col
+                        },
+                        span: SourceSpan::new(
+                            t.span.source_id,
+                            (t.span.start as isize + delta) as usize,
+                            (t.span.end as isize + delta) as usize,
+                        ),
+                        ..t.clone()
+                    }));
+                    return tokens;
+                }
+            }
+            tokens.push(token);
+        }
+        tokens
+    }
+
+    pub fn match_token(&self, input: &'i str, start: usize) -> Option<TokenPos> {
+        (self.literal_matcher)(input, start)
+    }
+
+    fn ends_statement(&self, token: &Token) -> bool {
+        self.config.automatic_semicolons
+            && matches!(
+                token.token_type,
+                TokenType::Spec(Rbrace)
+                    | TokenType::Spec(Rparen)
+                    | TokenType::Spec(Rbracket)
+                    | TokenType::Literal(Ident)
+                    | TokenType::Literal(Int)
+                    | TokenType::Literal(Float)
+                    | TokenType::Literal(Hex)
+                    | TokenType::Literal(Bin)
+                    | TokenType::Literal(Oct)
+                    | TokenType::Literal(Str)
+                    | TokenType::Literal(Char)
+                    | TokenType::Keyword(True)
+                    | TokenType::Keyword(False)
+                    | TokenType::Keyword(Null)
+            )
+    }
+}
+
+impl Default for Lexer {
+    fn default() -> Self {
+        Lexer::new()
+    }
+}
+
+impl LexerBuilder {
+    pub fn new() -> LexerBuilder {
+        LexerBuilder::default()
+    }
+
+    pub fn with_config(&mut self, config: LexerConfig) -> &mut Self {
+        self.config = config;
+        self
+    }
+
+    /// Lex the identifier `kw` as `token_type`; overrides built-in keywords too.
+    pub fn with_keyword(&mut self, kw: &str, token_type: TokenType) -> &mut Self {
+        self.keywords.push((kw.to_string(), token_type));
+        self
+    }
+
+    /// Patterns are tried in insertion order before the built-in literals,
+    /// but after the `Spec` symbols.
+    pub fn with_pattern(&mut self, regex_str: &str, token_type: TokenType) -> &mut Self {
+        self.patterns.push((regex_str.to_string(), token_type));
+        self
+    }
+
+    pub fn build(self) -> Result<Lexer<BuiltMatcher>, regex::Error> {
+        let patterns = self
+            .patterns
+            .into_iter()
+            .map(|(p, tt)| Regex::new(&format!("^(?:{})", p)).map(|r| (r, tt)))
+            .collect::<Result<Vec<_>, _>>()?;
+        let keywords = self.keywords;
+        let matcher = move |input: &str, start: usize| {
+            let rest = &input[start..];
+            for (r, tt) in patterns.iter() {
+                if let Some(m) = r.find(rest).filter(|m| !m.as_str().is_empty()) {
+                    return Some(TokenPos::new(*tt, start, start + m.end()));
+                }
+            }
+            let pos = TokenType::match_literal(input, start)?;
+            if let TokenType::Literal(Ident) | TokenType::Keyword(_) = pos.token_type {
+                let word = &input[pos.start..pos.end];
+                if let Some((_, tt)) = keywords.iter().find(|(kw, _)| kw == word) {
+                    return Some(TokenPos::new(*tt, pos.start, pos.end));
+                }
+            }
+            Some(pos)
+        };
+        Ok(Lexer::with_matcher(Box::new(matcher), self.config))
+    }
+}
+
+impl<'l, 'a, M> LexerIterator<'l, 'a, M>
+where
+    M: Fn(&str, usize) -> Option<TokenPos>,
+{
+    pub fn with_source_id(mut self, id: u32) -> Self {
+        self.source_id = id;
+        self
+    }
+
+    /// Drains the iterator; the errors include those already yielded by `next`.
+    pub fn collect_split(mut self) -> (Vec<Token>, Vec<LexError>) {
+        let tokens = self.by_ref().filter_map(Result::ok).collect();
+        (tokens, self.errors)
+    }
+
+    pub fn into_lossy(self) -> LossyIterator<'l, 'a, M> {
+        LossyIterator { inner: self }
+    }
+
+    /// Wraps the lossy iterator so that it always ends with a single `Eof` sentinel.
+    pub fn into_eof_terminated(self) -> EofTerminated<LossyIterator<'l, 'a, M>> {
+        EofTerminated::new(self.into_lossy())
+    }
+
+    fn span(&self, start: usize, end: usize) -> SourceSpan {
+        SourceSpan::new(self.source_id, start, end)
+    }
+
+    fn skip_whitespaces(&mut self) -> Option<char> {
+        loop {
+            let mut chars = self.input[self.pos..].chars().peekable();
+            let next = loop {
+                match chars.next() {
+                    Some('\r') if chars.peek() == Some(&'\n') => {
+                        chars.next();
+                        self.pos += 2;
+                        self.current_line += 1;
+                        self.current_col = 0;
+                    }
+                    Some('\r') | Some('\n') => {
+                        self.pos += 1;
+                        self.current_line += 1;
+                        self.current_col = 0;
+                    }
+                    Some(ch) if ch.is_whitespace() => {
+                        self.pos += ch.len_utf8();
+                        self.current_col += ch.len_utf8();
+                    }
+                    other => break other,
+                }
+            };
+            let rest = &self.input[self.pos..];
+            if rest.starts_with("//") {
+                self.skip_line_comment();
+            } else if rest.starts_with("/*") && rest[2..].contains("*/") {
+                self.skip_block_comment();
+            } else {
+                return next;
+            }
+        }
+    }
+
+    // Block comments don't nest: `/* /* */ */` ends at the first `*/`.
+    fn skip_block_comment(&mut self) {
+        let len = self.input[self.pos + 2..].find("*/").unwrap_or(0) + 4;
+        let comment = &self.input[self.pos..self.pos + len];
+        match line_breaks(comment) {
+            (count, Some(line_start)) => {
+                self.current_line += count;
+                self.current_col = len - line_start;
+            }
+            _ => self.current_col += len,
+        }
+        self.pos += len;
+    }
+
+    fn skip_line_comment(&mut self) {
+        let rest = &self.input[self.pos..];
+        match rest.find(['\n', '\r']) {
+            Some(i) => {
+                self.pos += i + if rest[i..].starts_with("\r\n") { 2 } else { 1 };
+                self.current_line += 1;
+                self.current_col = 0;
+            }
+            None => {
+                self.current_col += self.input.len() - self.pos;
+                self.pos = self.input.len();
+            }
+        }
+    }
+
+    fn produce(&mut self, pos: TokenPos) -> Token {
+        let col = self.current_col;
+        self.pos = pos.end;
+        self.current_col += pos.end - pos.start;
+        let (line, source_id) = (self.current_line, self.source_id);
+        match pos.token_type {
+            TokenType::Literal(_) => {
+                pos.token(Some(&self.input[pos.start..pos.end]), line, col, source_id)
+            }
+            _ => pos.token(None, line, col, source_id),
+        }
+    }
+
+    fn produce_string(&mut self) -> Token {
+        let (start, line, col) = (self.pos, self.current_line, self.current_col);
+        let mut chars = self.input[start..].char_indices().skip(1).peekable();
+        let mut literal = String::new();
+        let mut valid = true;
+        let mut line_start = None;
+        let (end, closed) = loop {
+            let (i, ch) = match chars.next() {
+                Some(next) => next,
+                None => break (self.input.len(), false),
+            };
+            match ch {
+                '"' => break (start + i + 1, true),
+                '\n' | '\r' => break (start + i, false),
+                '\\' => match chars.next() {
+                    Some((_, 'n')) => literal.push('\n'),
+                    Some((_, 't')) => literal.push('\t'),
+                    Some((_, 'r')) => literal.push('\r'),
+                    Some((_, '\\')) => literal.push('\\'),
+                    Some((_, '"')) => literal.push('"'),
+                    Some((j, '\n')) => {
+                        self.current_line += 1;
+                        line_start = Some(start + j + 1);
+                    }
+                    Some((j, '\r')) => {
+                        let crlf = chars.next_if(|&(_, ch)| ch == '\n').is_some();
+                        self.current_line += 1;
+                        line_start = Some(start + j + 1 + crlf as usize);
+                    }
+                    Some(_) => valid = false,
+                    None => break (self.input.len(), false),
+                },
+                _ => literal.push(ch),
+            }
+        };
+        self.current_col = match line_start {
+            Some(line_start) => end - line_start,
+            None => col + end - start,
+        };
+        self.pos = end;
+        let span = self.span(start, end);
+        if closed && valid {
+            Token::new(TokenType::Literal(Str), Some(literal), line, col, span)
+        } else {
+            Token::new(TokenType::Illegal, None, line, col, span)
+        }
+    }
+
+    // `'x'` holds exactly one character or escape; anything else up to the closing quote
+    // or the end of the line is `Illegal`.
+    fn produce_char(&mut self) -> Token {
+        let (start, col) = (self.pos, self.current_col);
+        let mut chars = self.input[start..].char_indices().skip(1);
+        let mut decoded = Vec::new();
+        let (end, closed) = loop {
+            match chars.next() {
+                Some((i, '\'')) => break (start + i + 1, true),
+                Some((i, '\n')) | Some((i, '\r')) => break (start + i, false),
+                Some((_, '\\')) => match chars.next() {
+                    Some((_, 'n')) => decoded.push(Some('\n')),
+                    Some((_, 't')) => decoded.push(Some('\t')),
+                    Some((_, 'r')) => decoded.push(Some('\r')),
+                    Some((_, ch @ '\\')) | Some((_, ch @ '\'')) | Some((_, ch @ '"')) => {
+                        decoded.push(Some(ch))
+                    }
+                    Some((i, '\n')) | Some((i, '\r')) => break (start + i, false),
+                    Some(_) => decoded.push(None),
+                    None => break (self.input.len(), false),
+                },
+                Some((_, ch)) => decoded.push(Some(ch)),
+                None => break (self.input.len(), false),
+            }
+        };
+        self.current_col += end - start;
+        self.pos = end;
+        let span = self.span(start, end);
+        match decoded.as_slice() {
+            [Some(ch)] if closed => Token::new(
+                TokenType::Literal(Char),
+                Some(ch.to_string()),
+                self.current_line,
+                col,
+                span,
+            ),
+            _ => Token::new(TokenType::Illegal, None, self.current_line, col, span),
+        }
+    }
+
+    // `"""..."""` keeps its content verbatim, line breaks included, and ends at the first
+    // `"""` after the opening one.
+    fn produce_triple_string(&mut self) -> Token {
+        let (start, line, col) = (self.pos, self.current_line, self.current_col);
+        let content = start + 3;
+        let (end, closed) = match self.input[content..].find("\"\"\"") {
+            Some(i) => (content + i + 3, true),
+            None => (self.input.len(), false),
+        };
+        match line_breaks(&self.input[start..end]) {
+            (count, Some(line_start)) => {
+                self.current_line += count;
+                self.current_col = end - start - line_start;
+            }
+            _ => self.current_col += end - start,
+        }
+        self.pos = end;
+        let span = self.span(start, end);
+        if closed {
+            let literal = self.input[content..end - 3].to_string();
+            Token::new(TokenType::Literal(Str), Some(literal), line, col, span)
+        } else {
+            Token::new(TokenType::Illegal, None, line, col, span)
+        }
+    }
+
+    // `r"..."` ends at the first `"`; backslashes are kept verbatim, so a raw string
+    // cannot contain a `"`. Like ordinary strings it must not span lines.
+    fn produce_raw_string(&mut self) -> Token {
+        let (start, line, col) = (self.pos, self.current_line, self.current_col);
+        let content = start + 2;
+        let (end, closed) = match self.input[content..].find(['"', '\n', '\r']) {
+            Some(i) if self.input[content + i..].starts_with('"') => (content + i + 1, true),
+            Some(i) => (content + i, false),
+            None => (self.input.len(), false),
+        };
+        self.current_col += end - start;
+        self.pos = end;
+        let span = self.span(start, end);
+        if closed {
+            let literal = self.input[content..end - 1].to_string();
+            Token::new(TokenType::Literal(Str), Some(literal), line, col, span)
+        } else {
+            Token::new(TokenType::Illegal, None, line, col, span)
+        }
+    }
+
+    fn illegal_or_none(&mut self) -> Option<Token> {
+        if self.pos < self.input.len() {
+            let (start, line, col) = (self.pos, self.current_line, self.current_col);
+            let rest = &self.input[start..];
+            match line_breaks(rest) {
+                (count, Some(line_start)) => {
+                    self.current_line += count;
+                    self.current_col = rest.len() - line_start;
+                }
+                _ => self.current_col += rest.len(),
+            }
+            self.pos = self.input.len();
+            return Some(Token {
+                token_type: TokenType::Illegal,
+                literal: None,
+                line,
+                col,
+                span: self.span(start, self.pos),
+            });
+        }
+        None
+    }
+
+    fn eof(&mut self) -> Option<Token> {
+        if self.eof_emitted {
+            return None;
+        }
+        self.eof_emitted = true;
+        let span = self.span(self.input.len(), self.input.len());
+        Some(Token::new(
+            TokenType::Eof,
+            None,
+            self.current_line,
+            self.current_col,
+            span,
+        ))
+    }
+
+    /// Looks at the token `next_token` returns next. A lexer error shows up here as a
+    /// `TokenType::Illegal` token, while `next` reports the same token as `Err(LexError)`.
+    pub fn peek(&mut self) -> Option<&Token> {
+        if self.peeked.is_none() {
+            self.peeked = self.lex_token();
+        }
+        self.peeked.as_ref()
+    }
+
+    pub fn span_of_next(&mut self) -> Option<SourceSpan> {
+        self.peek().map(|token| token.span)
+    }
+
+    pub fn next_token(&mut self) -> Option<Token> {
+        self.peeked.take().or_else(|| self.lex_token())
+    }
+
+    fn lex_token(&mut self) -> Option<Token> {
+        let (start, line, col) = (self.pos, self.current_line, self.current_col);
+        let next = self.skip_whitespaces();
+        if self.insert_semicolon && next.is_some() && self.current_line > line {
+            self.insert_semicolon = false;
+            let newline = start + self.input[start..].find(['\n', '\r']).unwrap_or(0);
+            let (col, span) = (col + newline - start, self.span(newline, newline + 1));
+            return Some(Token::new(
+                TokenType::Spec(Semicolon),
+                None,
+                line,
+                col,
+                span,
+            ));
+        }
+        let token = match next {
+            Some('"') if self.input[self.pos..].starts_with("\"\"\"") => {
+                Some(self.produce_triple_string())
+            }
+            Some('"') => Some(self.produce_string()),
+            Some('\'') => Some(self.produce_char()),
+            Some('r') if self.input[self.pos..].starts_with("r\"") => {
+                Some(self.produce_raw_string())
+            }
+            Some('/') if self.input[self.pos..].starts_with("/*") => self.illegal_or_none(),
+            _ => next
+                .and_then(|ch| TokenType::match_spec(self.input, self.pos, ch))
+                .or_else(|| self.lexer.match_token(self.input, self.pos))
+                .map(|m| self.produce(m))
+                .or_else(|| self.illegal_or_none())
+                .or_else(|| self.eof()),
+        };
+        self.insert_semicolon = token.as_ref().is_some_and(|t| self.lexer.ends_statement(t));
+        token
+    }
+}
+
+fn is_virtual_semicolon(token: &Token, source: &str) -> bool {
+    token.token_type == TokenType::Spec(Semicolon)
+        && source.get(token.span.start..token.span.end) != Some(";")
+}
+
+// Counts `\n`, `\r\n` and lone `\r` line breaks; also returns the offset just past the last one.
+fn line_breaks(text: &str) -> (usize, Option<usize>) {
+    let bytes = text.as_bytes();
+    let (mut count, mut line_start, mut i) = (0, None, 0);
+    while i < bytes.len() {
+        match bytes[i] {
+            b'\r' if bytes.get(i + 1) == Some(&b'\n') => i += 1,
+            b'\r' | b'\n' => {}
+            _ => {
+                i += 1;
+                continue;
+            }
+        }
+        i += 1;
+        count += 1;
+        line_start = Some(i);
+    }
+    (count, line_start)
+}
+
+impl<'l, 'a, M> Iterator for LexerIterator<'l, 'a, M>
+where
+    M: Fn(&str, usize) -> Option<TokenPos>,
+{
+    type Item = Result<Token, LexError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let token = self.next_token()?;
+        if token.token_type != TokenType::Illegal {
+            return Some(Ok(token));
+        }
+        let ch = self.input[token.span.start..]
+            .chars()
+            .next()
+            .unwrap_or('\0');
+        let err = LexError {
+            byte_offset: token.span.start,
+            line: token.line,
+            col: token.col,
+            ch,
+        };
+        self.errors.push(err);
+        Some(Err(err))
+    }
+}
+
+impl<'l, 'a, M> Iterator for LossyIterator<'l, 'a, M>
+where
+    M: Fn(&str, usize) -> Option<TokenPos>,
+{
+    type Item = Token;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.inner.next_token()
+    }
+}
+
+impl fmt::Display for LexError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "unexpected character {:?} at {}:{}",
+            self.ch,
+            self.line + 1,
+            self.col + 1
+        )
+    }
+}
+
+impl Error for LexError {}
+
+impl<I> EofTerminated<I>
+where
+    I: Iterator<Item = Token>,
+{
+    pub fn new(inner: I) -> EofTerminated<I> {
+        EofTerminated {
+            inner,
+            last: None,
+            done: false,
+        }
+    }
+}
+
+impl<I> Iterator for EofTerminated<I>
+where
+    I: Iterator<Item = Token>,
+{
+    type Item = Token;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+        match self.inner.next() {
+            Some(token) if token.token_type == TokenType::Eof => {
+                self.done = true;
+                Some(token)
+            }
+            Some(token) => {
+                self.last = Some((token.line, token.col, token.span));
+                Some(token)
+            }
+            None => {
+                self.done = true;
+                let (line, col, span) = match self.last {
+                    Some((line, col, s)) => (
+                        line,
+                        col + s.len(),
+                        SourceSpan::new(s.source_id, s.end, s.end),
+                    ),
+                    None => (0, 0, SourceSpan::default()),
+                };
+                Some(Token::new(TokenType::Eof, None, line, col, span))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::lexer::{EofTerminated, LexError, Lexer, LexerBuilder, LexerConfig, TextChange};
+    use crate::token::Keyword::*;
+    use crate::token::Literal::*;
+    use crate::token::Spec::*;
+    use crate::token::TokenType::*;
+    use crate::token::{SourceSpan, Token, TokenPos, TokenType};
+    use proptest::collection::vec;
+    use proptest::prelude::*;
+    use proptest::sample::{select, Index};
+
+    #[test]
+    fn test_illegal() {
+        let code = "-66 💥💥";
+        assert_tokens(code, &TEST_ILLEGAL);
+    }
+
+    #[test]
+    fn test1() {
+        let code = "=+(){},;";
+        assert_tokens(code, &TEST_1);
+    }
+
+    #[test]
+    fn test2() {
+        let code = "let x = 5 + 5;";
+        assert_tokens(code, &TEST_2);
+    }
+
+    #[test]
+    fn test3() {
+        let code = "let five = 5; \n\
+            let ten = 10; \n\
+            \n\
+            let add = fn(x, y) { \n\
+                x + y; \n\
+            }; \n\
+            let result = add(five, ten);";
+        assert_tokens(code, &TEST_3);
+    }
+
+    #[test]
+    fn test4() {
+        let code = "let five = 5; \n\
+            let ten = 10; \n\
+            let add = fn(x, y) { \n\
+                x + y; \n\
+            }; \n\
+            let result = add(five, ten); \n\
+            !-/*5; \n\
+            5 < 10 > 5; \n\
+            if (5 == 10) { \n\
+                return true; \n\
+            } else { \n\
+                return false; \n\
+            }";
+        // `/*` opens a block comment that is never closed, so everything from `/` on is
+        // a single `Illegal`.
+        let comment = TEST_4
+            .iter()
+            .position(|t| t.token_type == Spec(Slash))
+            .unwrap();
+        let mut expected = TEST_4[..comment].to_vec();
+        expected.push(TestToken::new(Illegal, None, 6));
+        expected.push(TestToken::new(Eof, None, 12));
+        assert_tokens(code, &expected);
+    }
+
+    #[test]
+    fn test4_slash_asterisk() {
+        let code = "let five = 5; \n\
+            let ten = 10; \n\
+            let add = fn(x, y) { \n\
+                x + y; \n\
+            }; \n\
+            let result = add(five, ten); \n\
+            !-/ *5; \n\
+            5 < 10 > 5; \n\
+            if (5 == 10) { \n\
+                return true; \n\
+            } else { \n\
+                return false; \n\
+            }";
+        assert_tokens(code, &TEST_4);
+    }
+
+    #[test]
+    fn test_struct() {
+        let code = "struct Point { x, y }";
+        assert_tokens(code, &TEST_STRUCT);
+    }
+
+    #[test]
+    fn test_question() {
+        let code = "a?.b?.c ? d?(0)";
+        assert_tokens(code, &TEST_QUESTION);
+    }
+
+    #[test]
+    fn test_question_question() {
+        let code = "a ?? b??7 ???";
+        assert_tokens(code, &TEST_QUESTION_QUESTION);
+    }
+
+    #[test]
+    fn test_assert() {
+        let code = "assert 1 == 2; asserted";
+        assert_tokens(code, &TEST_ASSERT);
+    }
+
+    #[test]
+    fn test_span_of_next() {
+        let lexer = Lexer::new();
+        let mut iter = lexer.tokenize("if (x) {}");
+        assert_eq!(Some(SourceSpan::new(0, 0, 2)), iter.span_of_next());
+        assert_eq!(Some(SourceSpan::new(0, 0, 2)), iter.span_of_next());
+        assert_eq!(
+            Some(SourceSpan::new(0, 0, 2)),
+            iter.next_token().map(|t| t.span)
+        );
+        assert_eq!(Some(SourceSpan::new(0, 3, 4)), iter.span_of_next());
+        assert_eq!(Spec(Lparen), iter.next_token().unwrap().token_type);
+        assert_eq!(5, iter.count());
+
+        let mut iter = lexer.tokenize("  ");
+        assert_eq!(Some(SourceSpan::new(0, 2, 2)), iter.span_of_next());
+        assert_eq!(Some(Eof), iter.next_token().map(|t| t.token_type));
+        assert_eq!(None, iter.span_of_next());
+    }
+
+    #[test]
+    fn test_pipe() {
+        let code = "5 |> double|>inc |>> a | b";
+        assert_tokens(code, &TEST_PIPE);
+    }
+
+    #[test]
+    fn test_at() {
+        let code = "@double fn id(x) { x }";
+        assert_tokens(code, &TEST_AT);
+    }
+
+    #[test]
+    fn test_type() {
+        let code = "type Id = Int; types";
+        assert_tokens(code, &TEST_TYPE);
+    }
+
+    #[test]
+    fn test_automatic_semicolons() {
+        let lexer = Lexer::with_config(LexerConfig {
+            automatic_semicolons: true,
+        });
+        let kinds = |code| {
+            lexer
+                .tokenize_lossy(code)
+                .filter(|t| t.token_type != Eof)
+                .map(|t| (t.token_type, t.literal, t.line))
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(
+            kinds("let x = 5;\nlet y = x + 1"),
+            kinds("let x = 5\nlet y = x + 1")
+        );
+        assert_eq!(
+            kinds("let f = fn(x) {\n  f(x);\n};\nf(true);\nlet y = x +\n  1"),
+            kinds("let f = fn(x) {\n  f(x)\n}\nf(true)\nlet y = x +\n  1")
+        );
+
+        let semicolon = lexer.tokenize_lossy("x\n\ny").nth(1).unwrap();
+        assert_eq!(Spec(Semicolon), semicolon.token_type);
+        assert_eq!(0, semicolon.line);
+        assert_eq!(SourceSpan::new(0, 1, 2), semicolon.span);
+        assert_eq!(2, lexer.tokenize_lossy("x\n").count());
+        assert_eq!(3, Lexer::new().tokenize_lossy("x\ny").count());
+    }
+
+    #[test]
+    fn test_elvis() {
+        let code = "false ?: 5; a?:b ?? 0";
+        assert_tokens(code, &TEST_ELVIS);
+    }
+
+    #[test]
+    fn test_columns() {
+        let lexer = Lexer::new();
+        let cols: Vec<(usize, usize)> = lexer
+            .tokenize_lossy("let x = 10;\n  x != 5")
+            .map(|t| (t.line, t.col))
+            .collect();
+        assert_eq!(
+            vec![
+                (0, 0),
+                (0, 4),
+                (0, 6),
+                (0, 8),
+                (0, 10),
+                (1, 2),
+                (1, 4),
+                (1, 7),
+                (1, 8),
+            ],
+            cols
+        );
+    }
+
+    #[test]
+    fn test_string() {
+        let code = r#"let s = "hello world"; "a\"b\\c\nd\te\rf" "";"#;
+        assert_tokens(code, &TEST_STRING);
+    }
+
+    #[test]
+    fn test_triple_quoted_string() {
+        let lexer = Lexer::new();
+        let kinds = |code| {
+            lexer
+                .tokenize_lossy(code)
+                .map(|t| (t.token_type, t.literal, t.line, t.col))
+                .collect::<Vec<_>>()
+        };
+        let str = |s: &str, line, col| (Literal(Str), Some(s.to_string()), line, col);
+        assert_eq!(
+            vec![
+                (Keyword(Let), None, 0, 0),
+                (Literal(Ident), Some("s".to_string()), 0, 4),
+                (Spec(Assign), None, 0, 6),
+                str("first\n  second\r\nthird", 0, 8),
+                (Spec(Semicolon), None, 2, 8),
+                (Eof, None, 2, 9)
+            ],
+            kinds("let s = \"\"\"first\n  second\r\nthird\"\"\";")
+        );
+        assert_eq!(
+            vec![str(r#"say "hi", ""twice"" \n"#, 0, 0), (Eof, None, 0, 28)],
+            kinds(r#""""say "hi", ""twice"" \n""""#)
+        );
+        assert_eq!(
+            vec![str("", 0, 0), str("", 0, 7), (Eof, None, 0, 9)],
+            kinds("\"\"\"\"\"\" \"\"")
+        );
+        assert_eq!(
+            vec![(Illegal, None, 0, 2), (Eof, None, 1, 6)],
+            kinds("1 \"\"\"open\n\"\" end")
+                .into_iter()
+                .skip(1)
+                .collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn test_raw_string() {
+        let lexer = Lexer::new();
+        let kinds = |code| {
+            lexer
+                .tokenize_lossy(code)
+                .map(|t| (t.token_type, t.literal, t.col))
+                .collect::<Vec<_>>()
+        };
+        let raw = |s: &str, col| (Literal(Str), Some(s.to_string()), col);
+        assert_eq!(
+            vec![raw("hello\\nworld", 0), (Eof, None, 15)],
+            kinds(r#"r"hello\nworld""#)
+        );
+        assert_eq!(
+            vec![raw("", 0), raw("C:\\dir\\", 4), (Eof, None, 14)],
+            kinds(r#"r"" r"C:\dir\""#)
+        );
+        assert_eq!(
+            vec![
+                (Literal(Ident), Some("r".to_string()), 0),
+                raw("x", 2),
+                (Literal(Ident), Some("rrr".to_string()), 6),
+                (Literal(Str), Some("".to_string()), 9),
+                (Eof, None, 11)
+            ],
+            kinds(r#"r "x" rrr"""#)
+        );
+        assert_eq!(
+            vec![
+                (Illegal, None, 0),
+                (Literal(Ident), Some("x".to_string()), 0),
+                (Eof, None, 1)
+            ],
+            kinds("r\"ab\nx")
+        );
+        assert_eq!(vec![(Illegal, None, 0), (Eof, None, 4)], kinds("r\"ab"));
+    }
+
+    #[test]
+    fn test_char() {
+        let lexer = Lexer::new();
+        let kinds = |code| {
+            lexer
+                .tokenize_lossy(code)
+                .map(|t| (t.token_type, t.literal, t.col))
+                .collect::<Vec<_>>()
+        };
+        let char = |s: &str, col| (Literal(Char), Some(s.to_string()), col);
+        assert_eq!(
+            vec![
+                char("a", 0),
+                char("\n", 4),
+                char("\\", 9),
+                char("'", 14),
+                char("\"", 19),
+                char("🍅", 23),
+                (Eof, None, 29)
+            ],
+            kinds(r#"'a' '\n' '\\' '\'' '"' '🍅'"#)
+        );
+        assert_eq!(
+            vec![
+                (Illegal, None, 0),
+                (Spec(Comma), None, 4),
+                (Illegal, None, 6),
+                (Spec(Comma), None, 8),
+                (Illegal, None, 10),
+                (Eof, None, 14)
+            ],
+            kinds(r#"'ab', '', '\q'"#)
+        );
+        assert_eq!(
+            vec![
+                (Illegal, None, 0),
+                (Literal(Ident), Some("x".to_string()), 0),
+                (Eof, None, 1)
+            ],
+            kinds("'a\nx")
+        );
+        assert_eq!(vec![(Illegal, None, 0), (Eof, None, 2)], kinds("'\\"));
+    }
+
+    #[test]
+    fn test_string_illegal() {
+        let lexer = Lexer::new();
+        let kinds = |code| {
+            lexer
+                .tokenize_lossy(code)
+                .filter(|t| t.token_type != Eof)
+                .map(|t| (t.token_type, t.literal, t.line))
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(
+            vec![
+                (Illegal, None, 0),
+                (Literal(Ident), Some("x".to_string()), 1)
+            ],
+            kinds("\"abc\nx")
+        );
+        assert_eq!(vec![(Illegal, None, 0)], kinds("\"abc"));
+        assert_eq!(vec![(Illegal, None, 0)], kinds("\"abc\\"));
+        assert_eq!(
+            vec![(Illegal, None, 0), (Spec(Semicolon), None, 0)],
+            kinds(r#""\q";"#)
+        );
+        assert_eq!(
+            vec![
+                (Literal(Str), Some("abcdef".to_string()), 0),
+                (Literal(Ident), Some("x".to_string()), 1)
+            ],
+            kinds("\"abc\\\ndef\" x")
+        );
+        let x = lexer.tokenize_lossy("\"abc\\\ndef\" x").nth(1).unwrap();
+        assert_eq!(5, x.col);
+    }
+
+    #[test]
+    fn test_float() {
+        let code = "1.0 1.5e10 2.0E-3 3.14+1 1.5e 5.";
+        assert_tokens(code, &TEST_FLOAT);
+    }
+
+    #[test]
+    fn test_line_comment() {
+        let lexer = Lexer::new();
+        let plain: Vec<Token> = lexer.tokenize_lossy("let x = 5;").collect();
+        let commented: Vec<Token> = lexer
+            .tokenize_lossy("// comment\nlet x = 5; // x")
+            .collect();
+        assert_eq!(plain.len(), commented.len());
+        plain.iter().zip(commented).for_each(|(plain, commented)| {
+            assert_eq!(plain.token_type, commented.token_type);
+            assert_eq!(plain.literal, commented.literal);
+            assert_eq!(plain.line + 1, commented.line);
+        });
+
+        let code = "// one\n  // two\n\n10 / 2 //";
+        let tokens: Vec<Token> = lexer.tokenize_lossy(code).collect();
+        assert_eq!(4, tokens.len());
+        assert_eq!(Spec(Slash), tokens[1].token_type);
+        assert_eq!(3, tokens[1].line);
+        assert_eq!(1, lexer.tokenize_lossy("//").count());
+    }
+
+    #[test]
+    fn test_block_comment() {
+        let lexer = Lexer::new();
+        let kinds = |code| {
+            lexer
+                .tokenize_lossy(code)
+                .filter(|t| t.token_type != Eof)
+                .map(|t| (t.token_type, t.line, t.col))
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(vec![(Literal(Ident), 1, 0)], kinds("/* own line */\nx"));
+        assert_eq!(
+            vec![(Literal(Ident), 0, 0), (Literal(Ident), 2, 9)],
+            kinds("x /* one\ntwo\nthree */ y")
+        );
+        assert_eq!(
+            vec![
+                (Literal(Int), 0, 0),
+                (Spec(Plus), 0, 2),
+                (Literal(Int), 0, 12),
+                (Spec(Asterisk), 0, 14),
+                (Literal(Int), 0, 16)
+            ],
+            kinds("1 + /* 2 */ 3 * 4")
+        );
+        assert_eq!(
+            vec![(Literal(Int), 0, 0), (Illegal, 0, 2)],
+            kinds("1 /* never\nclosed")
+        );
+        assert_eq!(vec![(Illegal, 0, 0)], kinds("/*/"));
+        assert_eq!(
+            vec![(Spec(Asterisk), 0, 9), (Spec(Slash), 0, 10)],
+            kinds("/* /* */ */")
+        );
+    }
+
+    #[test]
+    fn test_hex() {
+        let code = "0xFF 0XDEAD 0x0+0xafg; 0x; 0x";
+        assert_tokens(code, &TEST_HEX);
+    }
+
+    #[test]
+    fn test_bin() {
+        let code = "0b0001 0B1010 0b1111111111111111111111111111111111111111111111111111111111111111; 0b; 0b2; 0b102";
+        assert_tokens(code, &TEST_BIN);
+    }
+
+    #[test]
+    fn test_oct() {
+        let code = "0o7 0o755 0O17; 0o9; 0o8; 0o";
+        assert_tokens(code, &TEST_OCT);
+    }
+
+    #[test]
+    fn test_peek() {
+        let lexer = Lexer::new();
+        let mut iter = lexer.tokenize("let x\n= 5;");
+        let first = iter.peek().cloned();
+        assert_eq!(Some(Keyword(Let)), first.as_ref().map(|t| t.token_type));
+        assert_eq!(first, iter.peek().cloned());
+        assert_eq!(first, iter.next_token());
+        assert_eq!(
+            Some(Literal(Ident)),
+            iter.next_token().map(|t| t.token_type)
+        );
+
+        let assign = iter.peek().cloned().unwrap();
+        assert_eq!(1, assign.line);
+        assert_eq!(Some(assign), iter.next_token());
+        assert_eq!(3, iter.count());
+
+        let mut iter = lexer.tokenize("");
+        assert_eq!(Some(Eof), iter.peek().map(|t| t.token_type));
+        assert_eq!(Some(Eof), iter.next_token().map(|t| t.token_type));
+        assert_eq!(None, iter.peek());
+        assert_eq!(None, iter.next_token());
+
+        let mut iter = lexer.tokenize("#");
+        assert_eq!(Some(Illegal), iter.peek().map(|t| t.token_type));
+        assert_eq!(
+            Some('#'),
+            iter.next().and_then(|item| item.err()).map(|e| e.ch)
+        );
+        assert_eq!(Some(Eof), iter.peek().map(|t| t.token_type));
+    }
+
+    #[test]
+    fn test_tokenize_all() {
+        let code = "let x = 5; 💥💥";
+        let lexer = Lexer::new();
+        let all = lexer.tokenize_all(code);
+        assert_eq!(lexer.tokenize_lossy(code).collect::<Vec<Token>>(), all);
+        assert_eq!(Illegal, all[5].token_type);
+
+        let valid = lexer.tokenize_valid(code);
+        assert_eq!(all[..5], valid[..5]);
+        assert_eq!(all[6], valid[5]);
+        assert_eq!(2, lexer.tokenize_valid("0x 1 0b").len());
+    }
+
+    #[test]
+    fn test_eof() {
+        let lexer = Lexer::new();
+        let tokens = lexer.tokenize_all("x;\n  ");
+        assert_eq!(3, tokens.len());
+        assert_eq!(
+            Token::new(Eof, None, 1, 2, SourceSpan::new(0, 5, 5)),
+            tokens[2]
+        );
+
+        let mut iter = lexer.tokenize_lossy("\"open");
+        assert_eq!(Some(Illegal), iter.next().map(|t| t.token_type));
+        assert_eq!(Some(Eof), iter.next().map(|t| t.token_type));
+        assert_eq!(None, iter.next());
+        assert_eq!(None, iter.next());
+    }
+
+    #[test]
+    fn test_into_eof_terminated() {
+        let lexer = Lexer::new();
+        let tokens: Vec<Token> = lexer.tokenize("let x").into_eof_terminated().collect();
+        assert_eq!(lexer.tokenize_all("let x"), tokens);
+
+        let filtered = lexer
+            .tokenize("x y")
+            .with_source_id(3)
+            .into_lossy()
+            .filter(|t| t.token_type != Eof);
+        let tokens: Vec<Token> = EofTerminated::new(filtered).collect();
+        assert_eq!(3, tokens.len());
+        assert_eq!(Eof, tokens[2].token_type);
+        assert_eq!(
+            Token::new(Eof, None, 0, 3, SourceSpan::new(3, 3, 3)),
+            tokens[2]
+        );
+    }
+
+    #[test]
+    fn test_lex_error() {
+        let lexer = Lexer::new();
+        let items: Vec<Result<Token, LexError>> = lexer.tokenize("x\n 0b2 #").collect();
+        assert_eq!(5, items.len());
+        assert_eq!(Ok(Literal(Ident)), items[0].as_ref().map(|t| t.token_type));
+        assert_eq!(Ok(Literal(Bin)), items[1].as_ref().map(|t| t.token_type));
+        let err = LexError {
+            byte_offset: 5,
+            line: 1,
+            col: 3,
+            ch: '2',
+        };
+        assert_eq!(Err(err), items[2]);
+        assert_eq!(Some('#'), items[3].as_ref().err().map(|e| e.ch));
+        assert_eq!("unexpected character '2' at 2:4", err.to_string());
+
+        let lossy: Vec<TokenType> = lexer
+            .tokenize_lossy("x\n 0b2 #")
+            .map(|t| t.token_type)
+            .collect();
+        assert_eq!(
+            vec![Literal(Ident), Literal(Bin), Illegal, Illegal, Eof],
+            lossy
+        );
+    }
+
+    #[test]
+    fn test_collect_split() {
+        let lexer = Lexer::new();
+        let mut iter = lexer.tokenize("0x let x = \"\\q\"; 0b;");
+        assert!(iter.next().unwrap().is_err());
+        let (tokens, errors) = iter.collect_split();
+        let types: Vec<TokenType> = tokens.iter().map(|t| t.token_type).collect();
+        assert_eq!(
+            vec![
+                Keyword(Let),
+                Literal(Ident),
+                Spec(Assign),
+                Spec(Semicolon),
+                Spec(Semicolon),
+                Eof
+            ],
+            types
+        );
+        let offsets: Vec<usize> = errors.iter().map(|e| e.byte_offset).collect();
+        assert_eq!(vec![0, 11, 17], offsets);
+
+        let (tokens, errors) = lexer.tokenize("let x;").collect_split();
+        assert_eq!(lexer.tokenize_all("let x;"), tokens);
+        assert!(errors.is_empty());
+    }
+
+    #[test]
+    fn test_line_endings() {
+        let lexer = Lexer::new();
+        let positions = |code| {
+            lexer
+                .tokenize_lossy(code)
+                .map(|t| (t.token_type, t.line, t.col))
+                .collect::<Vec<_>>()
+        };
+        let expected = vec![
+            (Keyword(Let), 0, 0),
+            (Literal(Ident), 0, 4),
+            (Spec(Semicolon), 0, 5),
+            (Literal(Ident), 1, 0),
+            (Literal(Ident), 3, 2),
+            (Eof, 3, 3),
+        ];
+        assert_eq!(expected, positions("let x;\ny // c\n\n  z"));
+        assert_eq!(expected, positions("let x;\r\ny // c\r\n\r\n  z"));
+        assert_eq!(expected, positions("let x;\ry // c\r\r  z"));
+        assert_eq!(
+            vec![(Literal(Ident), 0, 0), (Literal(Ident), 2, 3), (Eof, 2, 4)],
+            positions("a /*\r\n\r*/ b")
+        );
+        assert_eq!(
+            vec![(Literal(Str), 0, 0), (Literal(Ident), 1, 4), (Eof, 1, 5)],
+            positions("\"ab\\\r\ncd\" x")
+        );
+        assert_eq!(
+            vec![(Illegal, 0, 0), (Literal(Ident), 1, 0), (Eof, 1, 1)],
+            positions("\"ab\r\nx")
+        );
+
+        let lexer = Lexer::with_config(LexerConfig {
+            automatic_semicolons: true,
+        });
+        let semicolon = lexer.tokenize_lossy("x\r\ny").nth(1).unwrap();
+        assert_eq!(Spec(Semicolon), semicolon.token_type);
+        assert_eq!(SourceSpan::new(0, 1, 2), semicolon.span);
+        let semicolon = lexer.tokenize_lossy("x\ry").nth(1).unwrap();
+        assert_eq!((Spec(Semicolon), 0), (semicolon.token_type, semicolon.line));
+    }
+
+    #[test]
+    fn test_unicode_ident() {
+        let lexer = Lexer::new();
+        let tokens: Vec<(TokenType, Option<String>)> = lexer
+            .tokenize_lossy("let αβγ = 变量 + _y + x变😀")
+            .map(|t| (t.token_type, t.literal))
+            .collect();
+        let ident = |s: &str| (Literal(Ident), Some(s.to_string()));
+        assert_eq!(
+            vec![
+                (Keyword(Let), None),
+                ident("αβγ"),
+                (Spec(Assign), None),
+                ident("变量"),
+                (Spec(Plus), None),
+                ident("_y"),
+                (Spec(Plus), None),
+                ident("x变"),
+                (Illegal, None),
+                (Eof, None),
+            ],
+            tokens
+        );
+    }
+
+    #[test]
+    fn test_custom_matcher() {
+        let digits = |input: &str, start: usize| {
+            let len = input[start..].find(|ch: char| !ch.is_ascii_digit())?;
+            Some(TokenPos::new(Literal(Int), start, start + len)).filter(|_| len > 0)
+        };
+        let lexer = Lexer::with_matcher(digits, LexerConfig::default());
+        let tokens = lexer.tokenize_all("12 + x;");
+        assert_eq!(4, tokens.len());
+        assert_eq!(Some("12".to_string()), tokens[0].literal);
+        assert_eq!(Illegal, tokens[2].token_type);
+    }
+
+    #[test]
+    fn assert_clone_send_sync() {
+        fn is_clone<T: Clone>() {}
+        fn is_send<T: Send>() {}
+        fn is_sync<T: Sync>() {}
+        is_clone::<Lexer>();
+        is_send::<Lexer>();
+        is_sync::<Lexer>();
+
+        let lexer = Lexer::with_config(LexerConfig {
+            automatic_semicolons: true,
+        });
+        let cloned = lexer.clone();
+        let handle = std::thread::spawn(move || cloned.tokenize_all("let x = 1\n").len());
+        assert_eq!(
+            lexer.tokenize_all("let x = 1\n").len(),
+            handle.join().unwrap()
+        );
+    }
+
+    #[test]
+    fn test_lexer_builder() {
+        let mut builder = LexerBuilder::new();
+        builder
+            .with_keyword("fun", TokenType::Keyword(Function))
+            .with_keyword("let", TokenType::Literal(Ident))
+            .with_pattern("#[a-z]+", TokenType::Literal(Str));
+        let lexer = builder.build().unwrap();
+        let tokens = lexer.tokenize_all("fun let #tag");
+        let types: Vec<TokenType> = tokens.iter().map(|t| t.token_type).collect();
+        assert_eq!(
+            vec![
+                TokenType::Keyword(Function),
+                TokenType::Literal(Ident),
+                TokenType::Literal(Str),
+                TokenType::Eof,
+            ],
+            types
+        );
+        assert_eq!(Some("#tag".to_string()), tokens[2].literal);
+    }
+
+    #[test]
+    fn test_lexer_builder_invalid_pattern() {
+        let mut builder = LexerBuilder::new();
+        builder.with_pattern("(", TokenType::Illegal);
+        assert!(builder.build().is_err());
+    }
+
+    #[test]
+    fn test_lte_gte() {
+        // `<=>` is not an operator of its own: it lexes as `Lte` followed by `Gt`.
+        let code = "5 <= 10; 10 >= 5;\na < b > c;\na <== b;\na <=> b";
+        assert_tokens(code, &TEST_LTE_GTE);
+    }
+
+    #[test]
+    fn test_and_or() {
+        let code = "a && b;\na || b;\na |||> f;\na && b & c";
+        let lexer = Lexer::new();
+        assert_tokens(code, &TEST_AND_OR);
+
+        let end: Vec<TokenType> = lexer.tokenize_lossy("a &&").map(|t| t.token_type).collect();
+        assert_eq!(vec![Literal(Ident), Spec(And), Eof], end);
+    }
+
+    #[test]
+    fn test_compound_assign() {
+        let code = "x += 1; x -=-2;\nx *= 3; x /= 4; // x /= 5\nx + y; x +== y";
+        assert_tokens(code, &TEST_COMPOUND_ASSIGN);
+    }
+
+    #[test]
+    fn test_percent_star_star() {
+        let code = "10 % 3; 2 ** 8;\n2 * 8; 2 *** 8;\nx%**y";
+        assert_tokens(code, &TEST_PERCENT_STAR_STAR);
+    }
+
+    #[test]
+    fn test_brackets() {
+        let code = "arr[0];\nlet e = [];\n[1, 2, 3][";
+        assert_tokens(code, &TEST_BRACKETS);
+
+        let lexer = Lexer::with_config(LexerConfig {
+            automatic_semicolons: true,
+        });
+        let semicolon = lexer.tokenize_lossy("xs[1]\nys").nth(4).unwrap();
+        assert_eq!(Spec(Semicolon), semicolon.token_type);
+    }
+
+    #[test]
+    fn test_colon() {
+        let code = "let x: Int = 5;\nstd::io::stdin();\nlabel:\na:::b";
+        assert_tokens(code, &TEST_COLON);
+    }
+
+    #[test]
+    fn test_arrow() {
+        // Patterns match left to right, so `-->` is `Minus` followed by `Arrow`.
+        let code = "fn(x) -> Int { x - 1 }\na --> b; c->";
+        assert_tokens(code, &TEST_ARROW);
+    }
+
+    #[test]
+    fn test_dot_dot() {
+        // A lone `.` is not a token, so `1...` is `Int("1")`, `DotDot`, then an
+        // `Illegal` for the trailing `.`.
+        let code = "1..10; ..5; 1.5..n;\n1...";
+        let lexer = Lexer::new();
+        assert_tokens(code, &TEST_DOT_DOT);
+
+        let dot = lexer.tokenize("x . y").nth(1).unwrap();
+        assert_eq!(Some('.'), dot.err().map(|e| e.ch));
+    }
+
+    #[test]
+    fn test_while() {
+        let code = "while true {}\nwhileTrue while_";
+        assert_tokens(code, &TEST_WHILE);
+    }
+
+    #[test]
+    fn test_for_in() {
+        let code = "for i in list {}\nindex integer inside forest i n";
+        assert_tokens(code, &TEST_FOR_IN);
+    }
+
+    #[test]
+    fn test_break_continue() {
+        let code = "break; continue;\nbreakpoint continued";
+        assert_tokens(code, &TEST_BREAK_CONTINUE);
     }
-}
 
-impl<'l, 'a> LexerIterator<'l, 'a> {
-    fn skip_whitespaces(&mut self) -> Option<char> {
-        self.input[self.pos..].chars().find(|ch| {
-            if ch.is_whitespace() {
-                if ch.eq(&'\n') {
-                    self.current_line += 1;
-                }
-                self.pos += ch.len_utf8();
-                false
-            } else {
-                true
-            }
-        })
+    #[test]
+    fn test_null() {
+        let code = "null\nnullify\nnull == false";
+        assert_tokens(code, &TEST_NULL);
+
+        let lexer = Lexer::with_config(LexerConfig {
+            automatic_semicolons: true,
+        });
+        let semicolon = lexer.tokenize_lossy("x = null\ny").nth(3).unwrap();
+        assert_eq!(Spec(Semicolon), semicolon.token_type);
     }
 
-    fn produce(&mut self, pos: TokenPos) -> Token {
-        let start = self.pos;
-        self.pos = pos.end;
-        match pos.token_type {
-            TokenType::Literal(_) => {
-                pos.token(Some(&self.input[start..pos.end]), self.current_line)
-            }
-            _ => pos.token(None, self.current_line),
-        }
+    #[test]
+    fn test_const_struct() {
+        let code = "const X = 5; constant\nstruct Point {} structure";
+        assert_tokens(code, &TEST_CONST_STRUCT);
     }
 
-    fn illegal_or_none(&mut self) -> Option<Token> {
-        if self.pos < self.input.len() {
-            self.pos = self.input.len();
-            return Some(Token {
-                token_type: TokenType::Illegal,
-                literal: None,
-                line: self.current_line,
-            });
-        }
-        None
+    #[test]
+    fn test_match_import_mod() {
+        let code = "match x { }\nimport \"math\"\nmod util { }\nmatcher imports module";
+        assert_tokens(code, &TEST_MATCH_IMPORT_MOD);
     }
 
-    pub fn next_token(&mut self) -> Option<Token> {
-        self.skip_whitespaces()
-            .and_then(|ch| TokenType::match_spec(self.input, self.pos, ch))
-            .or_else(|| self.lexer.match_token(self.input, self.pos))
-            .map(|m| self.produce(m))
-            .or_else(|| self.illegal_or_none())
+    #[test]
+    fn test_digit_separators() {
+        let code = "1_000 1_000_000 1_0 1_000.5;\n100_ + 1__x";
+        assert_tokens(code, &TEST_DIGIT_SEPARATORS);
     }
-}
 
-impl<'l, 'a> Iterator for LexerIterator<'l, 'a> {
-    type Item = Token;
+    #[test]
+    fn test_separator_validation() {
+        let code = "1__000 + 1_0_0;\n2__0.5;";
+        let lexer = Lexer::new();
+        assert_tokens(code, &TEST_SEPARATOR_VALIDATION);
 
-    fn next(&mut self) -> Option<Self::Item> {
-        self.next_token()
+        let err = lexer.tokenize("x = 1__000;").nth(2).unwrap().unwrap_err();
+        assert_eq!((4, '1'), (err.byte_offset, err.ch));
+        let semicolon = lexer.tokenize_lossy("1__000;").nth(1).unwrap();
+        assert_eq!(SourceSpan::new(0, 6, 7), semicolon.span);
     }
-}
-
-#[cfg(test)]
-mod test {
-    use crate::lexer::Lexer;
-    use crate::token::Keyword::*;
-    use crate::token::Literal::*;
-    use crate::token::Spec::*;
-    use crate::token::TokenType::*;
-    use crate::token::{Token, TokenType};
 
     #[test]
-    fn test_illegal() {
-        let code = "-66 бррр";
-        let lexer = Lexer::new();
-        lexer.tokenize(code).enumerate().for_each(|(i, token)| {
-            TEST_ILLEGAL[i].assert_eq(token);
+    fn test_incremental_after_comment_line_break() {
+        let lexer = Lexer::with_config(LexerConfig {
+            automatic_semicolons: true,
         });
+        let old_source = "x /*\n*/ y";
+        let old_tokens = lexer.tokenize_all(old_source);
+        let change = TextChange {
+            start: 9,
+            end: 9,
+            new_text: "z".to_string(),
+        };
+        let tokens = lexer.tokenize_incremental(&old_tokens, old_source, "x /*\n*/ yz", change);
+        assert_eq!(lexer.tokenize_all("x /*\n*/ yz"), tokens);
     }
 
     #[test]
-    fn test1() {
-        let code = "=+(){},;";
-        let lexer = Lexer::new();
-        lexer.tokenize(code).enumerate().for_each(|(i, token)| {
-            TEST_1[i].assert_eq(token);
+    fn test_incremental_opening_comment() {
+        let lexer = Lexer::with_config(LexerConfig {
+            automatic_semicolons: true,
         });
+        let old_source = "x\n*/ y";
+        let old_tokens = lexer.tokenize_all(old_source);
+        let change = TextChange {
+            start: 1,
+            end: 1,
+            new_text: " /*".to_string(),
+        };
+        let tokens = lexer.tokenize_incremental(&old_tokens, old_source, "x /*\n*/ y", change);
+        assert_eq!(lexer.tokenize_all("x /*\n*/ y"), tokens);
     }
 
     #[test]
-    fn test2() {
-        let code = "let x = 5 + 5;";
+    fn test_incremental_trailing_separator() {
         let lexer = Lexer::new();
-        lexer.tokenize(code).enumerate().for_each(|(i, token)| {
-            TEST_2[i].assert_eq(token);
-        });
+        let old_source = "5_ x";
+        let old_tokens = lexer.tokenize_all(old_source);
+        assert_eq!(Illegal, old_tokens[1].token_type);
+        let change = TextChange {
+            start: 0,
+            end: 1,
+            new_text: String::new(),
+        };
+        let tokens = lexer.tokenize_incremental(&old_tokens, old_source, "_ x", change);
+        assert_eq!(lexer.tokenize_all("_ x"), tokens);
     }
 
     #[test]
-    fn test3() {
-        let code = "let five = 5; \n\
-            let ten = 10; \n\
-            \n\
-            let add = fn(x, y) { \n\
-                x + y; \n\
-            }; \n\
-            let result = add(five, ten);";
+    fn test_source_id() {
         let lexer = Lexer::new();
-        lexer.tokenize(code).enumerate().for_each(|(i, token)| {
-            TEST_3[i].assert_eq(token);
-        });
+        let first: Vec<Token> = lexer
+            .tokenize("let x = 5;")
+            .with_source_id(1)
+            .into_lossy()
+            .collect();
+        let second: Vec<Token> = lexer
+            .tokenize("let y = x;")
+            .with_source_id(2)
+            .into_lossy()
+            .collect();
+        assert!(first.iter().all(|t| t.source_id() == 1));
+        assert!(second.iter().all(|t| t.source_id() == 2));
+        assert!(lexer.tokenize_lossy("x").all(|t| t.source_id() == 0));
     }
 
     #[test]
-    fn test4() {
-        let code = "let five = 5; \n\
-            let ten = 10; \n\
-            let add = fn(x, y) { \n\
-                x + y; \n\
-            }; \n\
-            let result = add(five, ten); \n\
-            !-/*5; \n\
-            5 < 10 > 5; \n\
-            if (5 == 10) { \n\
-                return true; \n\
-            } else { \n\
-                return false; \n\
-            }";
+    fn test_incremental() {
+        let old = "let x = 5;\nlet y = x + 1;";
+        let new = "let xs = 5;\nlet y = xs + 1;";
         let lexer = Lexer::new();
-        lexer.tokenize(code).enumerate().for_each(|(i, token)| {
-            TEST_4[i].assert_eq(token);
-        });
+        let old_tokens: Vec<Token> = lexer.tokenize_lossy(old).collect();
+        let edited = old.replacen("x", "xs", 1);
+        let change = TextChange {
+            start: 4,
+            end: 5,
+            new_text: "xs".to_string(),
+        };
+        let tokens = lexer.tokenize_incremental(&old_tokens, old, &edited, change);
+        assert_eq!(
+            lexer.tokenize_lossy(&edited).collect::<Vec<Token>>(),
+            tokens
+        );
+
+        let change = TextChange {
+            start: 20,
+            end: 21,
+            new_text: "xs".to_string(),
+        };
+        let tokens = lexer.tokenize_incremental(&tokens, &edited, new, change);
+        assert_eq!(lexer.tokenize_lossy(new).collect::<Vec<Token>>(), tokens);
+    }
+
+    const FRAGMENTS: [&str; 33] = [
+        "let", "x", "fn", "5", "10", "=", "==", "!", "!=", "+", "{", "}", ";", " ", " ", "\n",
+        "бр", "/", "\"", "\\", "n", "r", ".", "e", "*", "0", "\r", "\r\n", "_", "/*", "*/", "0b",
+        "o",
+    ];
+
+    fn source(fragments: &[&str]) -> String {
+        fragments.concat()
+    }
+
+    fn boundary(fragments: &[&str], index: &Index) -> usize {
+        let i = index.index(fragments.len() + 1);
+        fragments[..i].iter().map(|f| f.len()).sum()
+    }
+
+    proptest! {
+        #[test]
+        fn test_incremental_matches_full(
+            old in vec(select(&FRAGMENTS[..]), 0..24),
+            new_text in vec(select(&FRAGMENTS[..]), 0..6),
+            a in any::<Index>(),
+            b in any::<Index>(),
+            automatic_semicolons in any::<bool>(),
+        ) {
+            let old_source = source(&old);
+            let (start, end) = {
+                let (a, b) = (boundary(&old, &a), boundary(&old, &b));
+                (a.min(b), a.max(b))
+            };
+            let new_text = source(&new_text);
+            let new_source = format!("{}{}{}", &old_source[..start], new_text, &old_source[end..]);
+
+            let lexer = Lexer::with_config(LexerConfig { automatic_semicolons });
+            let old_tokens: Vec<Token> = lexer.tokenize_lossy(&old_source).collect();
+            let change = TextChange { start, end, new_text };
+            let tokens = lexer.tokenize_incremental(&old_tokens, &old_source, &new_source, change);
+            prop_assert_eq!(lexer.tokenize_lossy(&new_source).collect::<Vec<Token>>(), tokens);
+        }
     }
 
     #[derive(Debug, Clone, Eq, PartialEq)]
@@ -178,21 +1742,29 @@ mod test {
                 line,
             }
         }
+    }
 
-        pub fn assert_eq(&self, token: Token) {
-            assert_eq!(self.token_type, token.token_type);
-            assert_eq!(self.literal.map(|s| s.to_owned()), token.literal);
-            assert_eq!(self.line, token.line);
-        }
+    // Lexes `code` and compares the whole token stream, `Eof` included, with `expected`.
+    fn assert_tokens(code: &str, expected: &[TestToken]) {
+        let expected = expected
+            .iter()
+            .map(|t| (t.token_type, t.literal.map(str::to_string), t.line))
+            .collect::<Vec<_>>();
+        let tokens = Lexer::new()
+            .tokenize_lossy(code)
+            .map(|t| (t.token_type, t.literal, t.line))
+            .collect::<Vec<_>>();
+        assert_eq!(expected, tokens);
     }
 
-    const TEST_ILLEGAL: [TestToken; 3] = [
+    const TEST_ILLEGAL: [TestToken; 4] = [
         TestToken::new(Spec(Minus), None, 0),
         TestToken::new(Literal(Int), Some("66"), 0),
         TestToken::new(Illegal, None, 0),
+        TestToken::new(Eof, None, 0),
     ];
 
-    const TEST_1: [TestToken; 8] = [
+    const TEST_1: [TestToken; 9] = [
         TestToken::new(Spec(Assign), None, 0),
         TestToken::new(Spec(Plus), None, 0),
         TestToken::new(Spec(Lparen), None, 0),
@@ -201,9 +1773,10 @@ mod test {
         TestToken::new(Spec(Rbrace), None, 0),
         TestToken::new(Spec(Comma), None, 0),
         TestToken::new(Spec(Semicolon), None, 0),
+        TestToken::new(Eof, None, 0),
     ];
 
-    const TEST_2: [TestToken; 7] = [
+    const TEST_2: [TestToken; 8] = [
         TestToken::new(Keyword(Let), None, 0),
         TestToken::new(Literal(Ident), Some("x"), 0),
         TestToken::new(Spec(Assign), None, 0),
@@ -211,9 +1784,10 @@ mod test {
         TestToken::new(Spec(Plus), None, 0),
         TestToken::new(Literal(Int), Some("5"), 0),
         TestToken::new(Spec(Semicolon), None, 0),
+        TestToken::new(Eof, None, 0),
     ];
 
-    const TEST_3: [TestToken; 36] = [
+    const TEST_3: [TestToken; 37] = [
         TestToken::new(Keyword(Let), None, 0),
         TestToken::new(Literal(Ident), Some("five"), 0),
         TestToken::new(Spec(Assign), None, 0),
@@ -250,9 +1824,10 @@ mod test {
         TestToken::new(Literal(Ident), Some("ten"), 6),
         TestToken::new(Spec(Rparen), None, 6),
         TestToken::new(Spec(Semicolon), None, 6),
+        TestToken::new(Eof, None, 6),
     ];
 
-    const TEST_4: [TestToken; 65] = [
+    const TEST_4: [TestToken; 66] = [
         TestToken::new(Keyword(Let), None, 0),
         TestToken::new(Literal(Ident), Some("five"), 0),
         TestToken::new(Spec(Assign), None, 0),
@@ -318,5 +1893,469 @@ mod test {
         TestToken::new(Keyword(False), None, 11),
         TestToken::new(Spec(Semicolon), None, 11),
         TestToken::new(Spec(Rbrace), None, 12),
+        TestToken::new(Eof, None, 12),
+    ];
+
+    const TEST_STRUCT: [TestToken; 8] = [
+        TestToken::new(Keyword(Struct), None, 0),
+        TestToken::new(Literal(Ident), Some("Point"), 0),
+        TestToken::new(Spec(Lbrace), None, 0),
+        TestToken::new(Literal(Ident), Some("x"), 0),
+        TestToken::new(Spec(Comma), None, 0),
+        TestToken::new(Literal(Ident), Some("y"), 0),
+        TestToken::new(Spec(Rbrace), None, 0),
+        TestToken::new(Eof, None, 0),
+    ];
+
+    const TEST_QUESTION: [TestToken; 12] = [
+        TestToken::new(Literal(Ident), Some("a"), 0),
+        TestToken::new(Spec(QuestionDot), None, 0),
+        TestToken::new(Literal(Ident), Some("b"), 0),
+        TestToken::new(Spec(QuestionDot), None, 0),
+        TestToken::new(Literal(Ident), Some("c"), 0),
+        TestToken::new(Spec(QuestionMark), None, 0),
+        TestToken::new(Literal(Ident), Some("d"), 0),
+        TestToken::new(Spec(QuestionMark), None, 0),
+        TestToken::new(Spec(Lparen), None, 0),
+        TestToken::new(Literal(Int), Some("0"), 0),
+        TestToken::new(Spec(Rparen), None, 0),
+        TestToken::new(Eof, None, 0),
+    ];
+
+    const TEST_QUESTION_QUESTION: [TestToken; 8] = [
+        TestToken::new(Literal(Ident), Some("a"), 0),
+        TestToken::new(Spec(QuestionQuestion), None, 0),
+        TestToken::new(Literal(Ident), Some("b"), 0),
+        TestToken::new(Spec(QuestionQuestion), None, 0),
+        TestToken::new(Literal(Int), Some("7"), 0),
+        TestToken::new(Spec(QuestionQuestion), None, 0),
+        TestToken::new(Spec(QuestionMark), None, 0),
+        TestToken::new(Eof, None, 0),
+    ];
+
+    const TEST_ASSERT: [TestToken; 7] = [
+        TestToken::new(Keyword(Assert), None, 0),
+        TestToken::new(Literal(Int), Some("1"), 0),
+        TestToken::new(Spec(Equal), None, 0),
+        TestToken::new(Literal(Int), Some("2"), 0),
+        TestToken::new(Spec(Semicolon), None, 0),
+        TestToken::new(Literal(Ident), Some("asserted"), 0),
+        TestToken::new(Eof, None, 0),
+    ];
+
+    const TEST_PIPE: [TestToken; 10] = [
+        TestToken::new(Literal(Int), Some("5"), 0),
+        TestToken::new(Spec(Pipe), None, 0),
+        TestToken::new(Literal(Ident), Some("double"), 0),
+        TestToken::new(Spec(Pipe), None, 0),
+        TestToken::new(Literal(Ident), Some("inc"), 0),
+        TestToken::new(Spec(Pipe), None, 0),
+        TestToken::new(Spec(Gt), None, 0),
+        TestToken::new(Literal(Ident), Some("a"), 0),
+        TestToken::new(Illegal, None, 0),
+        TestToken::new(Eof, None, 0),
+    ];
+
+    const TEST_AT: [TestToken; 11] = [
+        TestToken::new(Spec(At), None, 0),
+        TestToken::new(Literal(Ident), Some("double"), 0),
+        TestToken::new(Keyword(Function), None, 0),
+        TestToken::new(Literal(Ident), Some("id"), 0),
+        TestToken::new(Spec(Lparen), None, 0),
+        TestToken::new(Literal(Ident), Some("x"), 0),
+        TestToken::new(Spec(Rparen), None, 0),
+        TestToken::new(Spec(Lbrace), None, 0),
+        TestToken::new(Literal(Ident), Some("x"), 0),
+        TestToken::new(Spec(Rbrace), None, 0),
+        TestToken::new(Eof, None, 0),
+    ];
+
+    const TEST_TYPE: [TestToken; 7] = [
+        TestToken::new(Keyword(Type), None, 0),
+        TestToken::new(Literal(Ident), Some("Id"), 0),
+        TestToken::new(Spec(Assign), None, 0),
+        TestToken::new(Literal(Ident), Some("Int"), 0),
+        TestToken::new(Spec(Semicolon), None, 0),
+        TestToken::new(Literal(Ident), Some("types"), 0),
+        TestToken::new(Eof, None, 0),
+    ];
+
+    const TEST_ELVIS: [TestToken; 10] = [
+        TestToken::new(Keyword(False), None, 0),
+        TestToken::new(Spec(Elvis), None, 0),
+        TestToken::new(Literal(Int), Some("5"), 0),
+        TestToken::new(Spec(Semicolon), None, 0),
+        TestToken::new(Literal(Ident), Some("a"), 0),
+        TestToken::new(Spec(Elvis), None, 0),
+        TestToken::new(Literal(Ident), Some("b"), 0),
+        TestToken::new(Spec(QuestionQuestion), None, 0),
+        TestToken::new(Literal(Int), Some("0"), 0),
+        TestToken::new(Eof, None, 0),
+    ];
+
+    const TEST_STRING: [TestToken; 9] = [
+        TestToken::new(Keyword(Let), None, 0),
+        TestToken::new(Literal(Ident), Some("s"), 0),
+        TestToken::new(Spec(Assign), None, 0),
+        TestToken::new(Literal(Str), Some("hello world"), 0),
+        TestToken::new(Spec(Semicolon), None, 0),
+        TestToken::new(Literal(Str), Some("a\"b\\c\nd\te\rf"), 0),
+        TestToken::new(Literal(Str), Some(""), 0),
+        TestToken::new(Spec(Semicolon), None, 0),
+        TestToken::new(Eof, None, 0),
+    ];
+
+    const TEST_FLOAT: [TestToken; 11] = [
+        TestToken::new(Literal(Float), Some("1.0"), 0),
+        TestToken::new(Literal(Float), Some("1.5e10"), 0),
+        TestToken::new(Literal(Float), Some("2.0E-3"), 0),
+        TestToken::new(Literal(Float), Some("3.14"), 0),
+        TestToken::new(Spec(Plus), None, 0),
+        TestToken::new(Literal(Int), Some("1"), 0),
+        TestToken::new(Literal(Float), Some("1.5"), 0),
+        TestToken::new(Literal(Ident), Some("e"), 0),
+        TestToken::new(Literal(Int), Some("5"), 0),
+        TestToken::new(Illegal, None, 0),
+        TestToken::new(Eof, None, 0),
+    ];
+
+    const TEST_HEX: [TestToken; 11] = [
+        TestToken::new(Literal(Hex), Some("0xFF"), 0),
+        TestToken::new(Literal(Hex), Some("0XDEAD"), 0),
+        TestToken::new(Literal(Hex), Some("0x0"), 0),
+        TestToken::new(Spec(Plus), None, 0),
+        TestToken::new(Literal(Hex), Some("0xaf"), 0),
+        TestToken::new(Literal(Ident), Some("g"), 0),
+        TestToken::new(Spec(Semicolon), None, 0),
+        TestToken::new(Illegal, None, 0),
+        TestToken::new(Spec(Semicolon), None, 0),
+        TestToken::new(Illegal, None, 0),
+        TestToken::new(Eof, None, 0),
+    ];
+
+    const TEST_BIN: [TestToken; 12] = [
+        TestToken::new(Literal(Bin), Some("0b0001"), 0),
+        TestToken::new(Literal(Bin), Some("0B1010"), 0),
+        TestToken::new(
+            Literal(Bin),
+            Some("0b1111111111111111111111111111111111111111111111111111111111111111"),
+            0,
+        ),
+        TestToken::new(Spec(Semicolon), None, 0),
+        TestToken::new(Illegal, None, 0),
+        TestToken::new(Spec(Semicolon), None, 0),
+        TestToken::new(Literal(Bin), Some("0b"), 0),
+        TestToken::new(Illegal, None, 0),
+        TestToken::new(Spec(Semicolon), None, 0),
+        TestToken::new(Literal(Bin), Some("0b10"), 0),
+        TestToken::new(Literal(Int), Some("2"), 0),
+        TestToken::new(Eof, None, 0),
+    ];
+
+    const TEST_OCT: [TestToken; 12] = [
+        TestToken::new(Literal(Oct), Some("0o7"), 0),
+        TestToken::new(Literal(Oct), Some("0o755"), 0),
+        TestToken::new(Literal(Oct), Some("0O17"), 0),
+        TestToken::new(Spec(Semicolon), None, 0),
+        TestToken::new(Literal(Oct), Some("0o"), 0),
+        TestToken::new(Illegal, None, 0),
+        TestToken::new(Spec(Semicolon), None, 0),
+        TestToken::new(Literal(Oct), Some("0o"), 0),
+        TestToken::new(Illegal, None, 0),
+        TestToken::new(Spec(Semicolon), None, 0),
+        TestToken::new(Illegal, None, 0),
+        TestToken::new(Eof, None, 0),
+    ];
+
+    const TEST_LTE_GTE: [TestToken; 24] = [
+        TestToken::new(Literal(Int), Some("5"), 0),
+        TestToken::new(Spec(Lte), None, 0),
+        TestToken::new(Literal(Int), Some("10"), 0),
+        TestToken::new(Spec(Semicolon), None, 0),
+        TestToken::new(Literal(Int), Some("10"), 0),
+        TestToken::new(Spec(Gte), None, 0),
+        TestToken::new(Literal(Int), Some("5"), 0),
+        TestToken::new(Spec(Semicolon), None, 0),
+        TestToken::new(Literal(Ident), Some("a"), 1),
+        TestToken::new(Spec(Lt), None, 1),
+        TestToken::new(Literal(Ident), Some("b"), 1),
+        TestToken::new(Spec(Gt), None, 1),
+        TestToken::new(Literal(Ident), Some("c"), 1),
+        TestToken::new(Spec(Semicolon), None, 1),
+        TestToken::new(Literal(Ident), Some("a"), 2),
+        TestToken::new(Spec(Lte), None, 2),
+        TestToken::new(Spec(Assign), None, 2),
+        TestToken::new(Literal(Ident), Some("b"), 2),
+        TestToken::new(Spec(Semicolon), None, 2),
+        TestToken::new(Literal(Ident), Some("a"), 3),
+        TestToken::new(Spec(Lte), None, 3),
+        TestToken::new(Spec(Gt), None, 3),
+        TestToken::new(Literal(Ident), Some("b"), 3),
+        TestToken::new(Eof, None, 3),
+    ];
+
+    const TEST_AND_OR: [TestToken; 18] = [
+        TestToken::new(Literal(Ident), Some("a"), 0),
+        TestToken::new(Spec(And), None, 0),
+        TestToken::new(Literal(Ident), Some("b"), 0),
+        TestToken::new(Spec(Semicolon), None, 0),
+        TestToken::new(Literal(Ident), Some("a"), 1),
+        TestToken::new(Spec(Or), None, 1),
+        TestToken::new(Literal(Ident), Some("b"), 1),
+        TestToken::new(Spec(Semicolon), None, 1),
+        TestToken::new(Literal(Ident), Some("a"), 2),
+        TestToken::new(Spec(Or), None, 2),
+        TestToken::new(Spec(Pipe), None, 2),
+        TestToken::new(Literal(Ident), Some("f"), 2),
+        TestToken::new(Spec(Semicolon), None, 2),
+        TestToken::new(Literal(Ident), Some("a"), 3),
+        TestToken::new(Spec(And), None, 3),
+        TestToken::new(Literal(Ident), Some("b"), 3),
+        TestToken::new(Illegal, None, 3),
+        TestToken::new(Eof, None, 3),
+    ];
+
+    const TEST_COMPOUND_ASSIGN: [TestToken; 26] = [
+        TestToken::new(Literal(Ident), Some("x"), 0),
+        TestToken::new(Spec(PlusAssign), None, 0),
+        TestToken::new(Literal(Int), Some("1"), 0),
+        TestToken::new(Spec(Semicolon), None, 0),
+        TestToken::new(Literal(Ident), Some("x"), 0),
+        TestToken::new(Spec(MinusAssign), None, 0),
+        TestToken::new(Spec(Minus), None, 0),
+        TestToken::new(Literal(Int), Some("2"), 0),
+        TestToken::new(Spec(Semicolon), None, 0),
+        TestToken::new(Literal(Ident), Some("x"), 1),
+        TestToken::new(Spec(AsteriskAssign), None, 1),
+        TestToken::new(Literal(Int), Some("3"), 1),
+        TestToken::new(Spec(Semicolon), None, 1),
+        TestToken::new(Literal(Ident), Some("x"), 1),
+        TestToken::new(Spec(SlashAssign), None, 1),
+        TestToken::new(Literal(Int), Some("4"), 1),
+        TestToken::new(Spec(Semicolon), None, 1),
+        TestToken::new(Literal(Ident), Some("x"), 2),
+        TestToken::new(Spec(Plus), None, 2),
+        TestToken::new(Literal(Ident), Some("y"), 2),
+        TestToken::new(Spec(Semicolon), None, 2),
+        TestToken::new(Literal(Ident), Some("x"), 2),
+        TestToken::new(Spec(PlusAssign), None, 2),
+        TestToken::new(Spec(Assign), None, 2),
+        TestToken::new(Literal(Ident), Some("y"), 2),
+        TestToken::new(Eof, None, 2),
+    ];
+
+    const TEST_PERCENT_STAR_STAR: [TestToken; 22] = [
+        TestToken::new(Literal(Int), Some("10"), 0),
+        TestToken::new(Spec(Percent), None, 0),
+        TestToken::new(Literal(Int), Some("3"), 0),
+        TestToken::new(Spec(Semicolon), None, 0),
+        TestToken::new(Literal(Int), Some("2"), 0),
+        TestToken::new(Spec(StarStar), None, 0),
+        TestToken::new(Literal(Int), Some("8"), 0),
+        TestToken::new(Spec(Semicolon), None, 0),
+        TestToken::new(Literal(Int), Some("2"), 1),
+        TestToken::new(Spec(Asterisk), None, 1),
+        TestToken::new(Literal(Int), Some("8"), 1),
+        TestToken::new(Spec(Semicolon), None, 1),
+        TestToken::new(Literal(Int), Some("2"), 1),
+        TestToken::new(Spec(StarStar), None, 1),
+        TestToken::new(Spec(Asterisk), None, 1),
+        TestToken::new(Literal(Int), Some("8"), 1),
+        TestToken::new(Spec(Semicolon), None, 1),
+        TestToken::new(Literal(Ident), Some("x"), 2),
+        TestToken::new(Spec(Percent), None, 2),
+        TestToken::new(Spec(StarStar), None, 2),
+        TestToken::new(Literal(Ident), Some("y"), 2),
+        TestToken::new(Eof, None, 2),
+    ];
+
+    const TEST_BRACKETS: [TestToken; 20] = [
+        TestToken::new(Literal(Ident), Some("arr"), 0),
+        TestToken::new(Spec(Lbracket), None, 0),
+        TestToken::new(Literal(Int), Some("0"), 0),
+        TestToken::new(Spec(Rbracket), None, 0),
+        TestToken::new(Spec(Semicolon), None, 0),
+        TestToken::new(Keyword(Let), None, 1),
+        TestToken::new(Literal(Ident), Some("e"), 1),
+        TestToken::new(Spec(Assign), None, 1),
+        TestToken::new(Spec(Lbracket), None, 1),
+        TestToken::new(Spec(Rbracket), None, 1),
+        TestToken::new(Spec(Semicolon), None, 1),
+        TestToken::new(Spec(Lbracket), None, 2),
+        TestToken::new(Literal(Int), Some("1"), 2),
+        TestToken::new(Spec(Comma), None, 2),
+        TestToken::new(Literal(Int), Some("2"), 2),
+        TestToken::new(Spec(Comma), None, 2),
+        TestToken::new(Literal(Int), Some("3"), 2),
+        TestToken::new(Spec(Rbracket), None, 2),
+        TestToken::new(Spec(Lbracket), None, 2),
+        TestToken::new(Eof, None, 2),
+    ];
+
+    const TEST_COLON: [TestToken; 22] = [
+        TestToken::new(Keyword(Let), None, 0),
+        TestToken::new(Literal(Ident), Some("x"), 0),
+        TestToken::new(Spec(Colon), None, 0),
+        TestToken::new(Literal(Ident), Some("Int"), 0),
+        TestToken::new(Spec(Assign), None, 0),
+        TestToken::new(Literal(Int), Some("5"), 0),
+        TestToken::new(Spec(Semicolon), None, 0),
+        TestToken::new(Literal(Ident), Some("std"), 1),
+        TestToken::new(Spec(PathSep), None, 1),
+        TestToken::new(Literal(Ident), Some("io"), 1),
+        TestToken::new(Spec(PathSep), None, 1),
+        TestToken::new(Literal(Ident), Some("stdin"), 1),
+        TestToken::new(Spec(Lparen), None, 1),
+        TestToken::new(Spec(Rparen), None, 1),
+        TestToken::new(Spec(Semicolon), None, 1),
+        TestToken::new(Literal(Ident), Some("label"), 2),
+        TestToken::new(Spec(Colon), None, 2),
+        TestToken::new(Literal(Ident), Some("a"), 3),
+        TestToken::new(Spec(PathSep), None, 3),
+        TestToken::new(Spec(Colon), None, 3),
+        TestToken::new(Literal(Ident), Some("b"), 3),
+        TestToken::new(Eof, None, 3),
+    ];
+
+    const TEST_ARROW: [TestToken; 19] = [
+        TestToken::new(Keyword(Function), None, 0),
+        TestToken::new(Spec(Lparen), None, 0),
+        TestToken::new(Literal(Ident), Some("x"), 0),
+        TestToken::new(Spec(Rparen), None, 0),
+        TestToken::new(Spec(Arrow), None, 0),
+        TestToken::new(Literal(Ident), Some("Int"), 0),
+        TestToken::new(Spec(Lbrace), None, 0),
+        TestToken::new(Literal(Ident), Some("x"), 0),
+        TestToken::new(Spec(Minus), None, 0),
+        TestToken::new(Literal(Int), Some("1"), 0),
+        TestToken::new(Spec(Rbrace), None, 0),
+        TestToken::new(Literal(Ident), Some("a"), 1),
+        TestToken::new(Spec(Minus), None, 1),
+        TestToken::new(Spec(Arrow), None, 1),
+        TestToken::new(Literal(Ident), Some("b"), 1),
+        TestToken::new(Spec(Semicolon), None, 1),
+        TestToken::new(Literal(Ident), Some("c"), 1),
+        TestToken::new(Spec(Arrow), None, 1),
+        TestToken::new(Eof, None, 1),
+    ];
+
+    const TEST_DOT_DOT: [TestToken; 15] = [
+        TestToken::new(Literal(Int), Some("1"), 0),
+        TestToken::new(Spec(DotDot), None, 0),
+        TestToken::new(Literal(Int), Some("10"), 0),
+        TestToken::new(Spec(Semicolon), None, 0),
+        TestToken::new(Spec(DotDot), None, 0),
+        TestToken::new(Literal(Int), Some("5"), 0),
+        TestToken::new(Spec(Semicolon), None, 0),
+        TestToken::new(Literal(Float), Some("1.5"), 0),
+        TestToken::new(Spec(DotDot), None, 0),
+        TestToken::new(Literal(Ident), Some("n"), 0),
+        TestToken::new(Spec(Semicolon), None, 0),
+        TestToken::new(Literal(Int), Some("1"), 1),
+        TestToken::new(Spec(DotDot), None, 1),
+        TestToken::new(Illegal, None, 1),
+        TestToken::new(Eof, None, 1),
+    ];
+
+    const TEST_WHILE: [TestToken; 7] = [
+        TestToken::new(Keyword(While), None, 0),
+        TestToken::new(Keyword(True), None, 0),
+        TestToken::new(Spec(Lbrace), None, 0),
+        TestToken::new(Spec(Rbrace), None, 0),
+        TestToken::new(Literal(Ident), Some("whileTrue"), 1),
+        TestToken::new(Literal(Ident), Some("while_"), 1),
+        TestToken::new(Eof, None, 1),
+    ];
+
+    const TEST_FOR_IN: [TestToken; 13] = [
+        TestToken::new(Keyword(For), None, 0),
+        TestToken::new(Literal(Ident), Some("i"), 0),
+        TestToken::new(Keyword(In), None, 0),
+        TestToken::new(Literal(Ident), Some("list"), 0),
+        TestToken::new(Spec(Lbrace), None, 0),
+        TestToken::new(Spec(Rbrace), None, 0),
+        TestToken::new(Literal(Ident), Some("index"), 1),
+        TestToken::new(Literal(Ident), Some("integer"), 1),
+        TestToken::new(Literal(Ident), Some("inside"), 1),
+        TestToken::new(Literal(Ident), Some("forest"), 1),
+        TestToken::new(Literal(Ident), Some("i"), 1),
+        TestToken::new(Literal(Ident), Some("n"), 1),
+        TestToken::new(Eof, None, 1),
+    ];
+
+    const TEST_BREAK_CONTINUE: [TestToken; 7] = [
+        TestToken::new(Keyword(Break), None, 0),
+        TestToken::new(Spec(Semicolon), None, 0),
+        TestToken::new(Keyword(Continue), None, 0),
+        TestToken::new(Spec(Semicolon), None, 0),
+        TestToken::new(Literal(Ident), Some("breakpoint"), 1),
+        TestToken::new(Literal(Ident), Some("continued"), 1),
+        TestToken::new(Eof, None, 1),
+    ];
+
+    const TEST_NULL: [TestToken; 6] = [
+        TestToken::new(Keyword(Null), None, 0),
+        TestToken::new(Literal(Ident), Some("nullify"), 1),
+        TestToken::new(Keyword(Null), None, 2),
+        TestToken::new(Spec(Equal), None, 2),
+        TestToken::new(Keyword(False), None, 2),
+        TestToken::new(Eof, None, 2),
+    ];
+
+    const TEST_CONST_STRUCT: [TestToken; 12] = [
+        TestToken::new(Keyword(Const), None, 0),
+        TestToken::new(Literal(Ident), Some("X"), 0),
+        TestToken::new(Spec(Assign), None, 0),
+        TestToken::new(Literal(Int), Some("5"), 0),
+        TestToken::new(Spec(Semicolon), None, 0),
+        TestToken::new(Literal(Ident), Some("constant"), 0),
+        TestToken::new(Keyword(Struct), None, 1),
+        TestToken::new(Literal(Ident), Some("Point"), 1),
+        TestToken::new(Spec(Lbrace), None, 1),
+        TestToken::new(Spec(Rbrace), None, 1),
+        TestToken::new(Literal(Ident), Some("structure"), 1),
+        TestToken::new(Eof, None, 1),
+    ];
+
+    const TEST_MATCH_IMPORT_MOD: [TestToken; 14] = [
+        TestToken::new(Keyword(Match), None, 0),
+        TestToken::new(Literal(Ident), Some("x"), 0),
+        TestToken::new(Spec(Lbrace), None, 0),
+        TestToken::new(Spec(Rbrace), None, 0),
+        TestToken::new(Keyword(Import), None, 1),
+        TestToken::new(Literal(Str), Some("math"), 1),
+        TestToken::new(Keyword(Mod), None, 2),
+        TestToken::new(Literal(Ident), Some("util"), 2),
+        TestToken::new(Spec(Lbrace), None, 2),
+        TestToken::new(Spec(Rbrace), None, 2),
+        TestToken::new(Literal(Ident), Some("matcher"), 3),
+        TestToken::new(Literal(Ident), Some("imports"), 3),
+        TestToken::new(Literal(Ident), Some("module"), 3),
+        TestToken::new(Eof, None, 3),
+    ];
+
+    const TEST_DIGIT_SEPARATORS: [TestToken; 12] = [
+        TestToken::new(Literal(Int), Some("1_000"), 0),
+        TestToken::new(Literal(Int), Some("1_000_000"), 0),
+        TestToken::new(Literal(Int), Some("1_0"), 0),
+        TestToken::new(Literal(Float), Some("1_000.5"), 0),
+        TestToken::new(Spec(Semicolon), None, 0),
+        TestToken::new(Literal(Int), Some("100"), 1),
+        TestToken::new(Illegal, None, 1),
+        TestToken::new(Spec(Plus), None, 1),
+        TestToken::new(Literal(Int), Some("1"), 1),
+        TestToken::new(Illegal, None, 1),
+        TestToken::new(Literal(Ident), Some("x"), 1),
+        TestToken::new(Eof, None, 1),
+    ];
+
+    const TEST_SEPARATOR_VALIDATION: [TestToken; 7] = [
+        TestToken::new(Illegal, None, 0),
+        TestToken::new(Spec(Plus), None, 0),
+        TestToken::new(Literal(Int), Some("1_0_0"), 0),
+        TestToken::new(Spec(Semicolon), None, 0),
+        TestToken::new(Illegal, None, 1),
+        TestToken::new(Spec(Semicolon), None, 1),
+        TestToken::new(Eof, None, 1),
     ];
 }
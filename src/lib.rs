@@ -1,3 +1,4 @@
 pub mod lexer;
+pub mod parser;
 pub mod repl;
 pub mod token;
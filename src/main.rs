@@ -1,6 +1,61 @@
+use clap::{ArgGroup, Parser};
+use pomidor::lexer::Lexer;
 use pomidor::repl::Repl;
+use std::path::PathBuf;
+use std::process;
+
+/// Starts the REPL unless one of the options is given.
+#[derive(Parser)]
+#[command(version, about)]
+#[command(group(ArgGroup::new("mode").args(["eval", "file", "tokens"])))]
+struct Cli {
+    /// Lex a single expression and print the result
+    #[arg(long, value_name = "EXPR")]
+    eval: Option<String>,
+    /// Run a script file
+    #[arg(long, value_name = "PATH")]
+    file: Option<PathBuf>,
+    /// Print the tokens of an expression, one per line
+    #[arg(long, value_name = "EXPR")]
+    tokens: Option<String>,
+}
 
 pub fn main() {
-    let mut repl = Repl::new();
-    repl.run();
+    let cli = Cli::parse();
+    let lexer = Lexer::new();
+    if let Some(expr) = cli.eval {
+        lexer.tokenize(&expr).for_each(|item| match item {
+            Ok(token) => println!("{:?}", token),
+            Err(err) => println!("{}", err),
+        });
+    } else if let Some(path) = cli.file {
+        if let Err(err) = Repl::new().run_file(&path) {
+            eprintln!("pomidor: {}: {}", path.display(), err);
+            process::exit(1);
+        }
+    } else if let Some(expr) = cli.tokens {
+        lexer
+            .tokenize_lossy(&expr)
+            .for_each(|token| println!("{}", token));
+    } else {
+        let mut repl = Repl::new();
+        if let Err(err) = repl.run() {
+            eprintln!("pomidor: {}", err);
+            process::exit(1);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::Cli;
+    use clap::{CommandFactory, Parser};
+
+    #[test]
+    fn test_cli() {
+        Cli::command().debug_assert();
+        let cli = Cli::try_parse_from(["pomidor", "--tokens", "let x"]).unwrap();
+        assert_eq!(Some("let x".to_string()), cli.tokens);
+        assert!(Cli::try_parse_from(["pomidor", "--eval", "1", "--file", "a.monkey"]).is_err());
+    }
 }
@@ -0,0 +1,389 @@
+use crate::token::Token;
+
+pub trait Node {
+    fn token_literal(&self) -> &str;
+    fn as_string(&self) -> String;
+}
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum Statement {
+    Let(LetStatement),
+    Return(ReturnStatement),
+    Expression(ExpressionStatement),
+}
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct LetStatement {
+    pub token: Token,
+    pub name: Identifier,
+    pub value: Expression,
+}
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct ReturnStatement {
+    pub token: Token,
+    pub value: Expression,
+}
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct ExpressionStatement {
+    pub token: Token,
+    pub expression: Expression,
+}
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct BlockStatement {
+    pub token: Token,
+    pub statements: Vec<Statement>,
+}
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum Expression {
+    Identifier(Identifier),
+    Integer(IntegerLiteral),
+    Boolean(BooleanLiteral),
+    Function(FunctionLiteral),
+    Call(CallExpression),
+    Prefix(PrefixExpression),
+    Infix(InfixExpression),
+    If(IfExpression),
+    Null(NullLiteral),
+}
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct Identifier {
+    pub token: Token,
+    pub value: String,
+}
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct IntegerLiteral {
+    pub token: Token,
+    pub value: i64,
+}
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct BooleanLiteral {
+    pub token: Token,
+    pub value: bool,
+}
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct FunctionLiteral {
+    pub token: Token,
+    pub parameters: Vec<Identifier>,
+    pub body: BlockStatement,
+}
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct CallExpression {
+    pub token: Token,
+    pub function: Box<Expression>,
+    pub arguments: Vec<Expression>,
+}
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct PrefixExpression {
+    pub token: Token,
+    pub operator: String,
+    pub right: Box<Expression>,
+}
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct InfixExpression {
+    pub token: Token,
+    pub left: Box<Expression>,
+    pub operator: String,
+    pub right: Box<Expression>,
+}
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct IfExpression {
+    pub token: Token,
+    pub condition: Box<Expression>,
+    pub consequence: BlockStatement,
+    pub alternative: Option<BlockStatement>,
+}
+
+/// The `null` keyword, or the missing value of a bare `return;`.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct NullLiteral {
+    pub token: Token,
+}
+
+// The literal of an identifier or number, otherwise the operator or keyword text.
+fn token_text(token: &Token) -> &str {
+    token
+        .literal
+        .as_deref()
+        .or_else(|| token.token_type.as_str())
+        .unwrap_or("")
+}
+
+impl Node for Statement {
+    fn token_literal(&self) -> &str {
+        match self {
+            Statement::Let(statement) => statement.token_literal(),
+            Statement::Return(statement) => statement.token_literal(),
+            Statement::Expression(statement) => statement.token_literal(),
+        }
+    }
+
+    fn as_string(&self) -> String {
+        match self {
+            Statement::Let(statement) => statement.as_string(),
+            Statement::Return(statement) => statement.as_string(),
+            Statement::Expression(statement) => statement.as_string(),
+        }
+    }
+}
+
+impl Node for LetStatement {
+    fn token_literal(&self) -> &str {
+        token_text(&self.token)
+    }
+
+    fn as_string(&self) -> String {
+        format!(
+            "{} {} = {};",
+            self.token_literal(),
+            self.name.as_string(),
+            self.value.as_string()
+        )
+    }
+}
+
+impl Node for ReturnStatement {
+    fn token_literal(&self) -> &str {
+        token_text(&self.token)
+    }
+
+    fn as_string(&self) -> String {
+        format!("{} {};", self.token_literal(), self.value.as_string())
+    }
+}
+
+impl Node for ExpressionStatement {
+    fn token_literal(&self) -> &str {
+        token_text(&self.token)
+    }
+
+    fn as_string(&self) -> String {
+        self.expression.as_string()
+    }
+}
+
+impl Node for BlockStatement {
+    fn token_literal(&self) -> &str {
+        token_text(&self.token)
+    }
+
+    fn as_string(&self) -> String {
+        let statements = self
+            .statements
+            .iter()
+            .map(|s| s.as_string())
+            .collect::<Vec<_>>();
+        match statements.is_empty() {
+            true => "{}".to_string(),
+            false => format!("{{ {} }}", statements.join(" ")),
+        }
+    }
+}
+
+impl Node for Expression {
+    fn token_literal(&self) -> &str {
+        match self {
+            Expression::Identifier(expression) => expression.token_literal(),
+            Expression::Integer(expression) => expression.token_literal(),
+            Expression::Boolean(expression) => expression.token_literal(),
+            Expression::Function(expression) => expression.token_literal(),
+            Expression::Call(expression) => expression.token_literal(),
+            Expression::Prefix(expression) => expression.token_literal(),
+            Expression::Infix(expression) => expression.token_literal(),
+            Expression::If(expression) => expression.token_literal(),
+            Expression::Null(expression) => expression.token_literal(),
+        }
+    }
+
+    fn as_string(&self) -> String {
+        match self {
+            Expression::Identifier(expression) => expression.as_string(),
+            Expression::Integer(expression) => expression.as_string(),
+            Expression::Boolean(expression) => expression.as_string(),
+            Expression::Function(expression) => expression.as_string(),
+            Expression::Call(expression) => expression.as_string(),
+            Expression::Prefix(expression) => expression.as_string(),
+            Expression::Infix(expression) => expression.as_string(),
+            Expression::If(expression) => expression.as_string(),
+            Expression::Null(expression) => expression.as_string(),
+        }
+    }
+}
+
+impl Node for Identifier {
+    fn token_literal(&self) -> &str {
+        token_text(&self.token)
+    }
+
+    fn as_string(&self) -> String {
+        self.value.clone()
+    }
+}
+
+impl Node for IntegerLiteral {
+    fn token_literal(&self) -> &str {
+        token_text(&self.token)
+    }
+
+    fn as_string(&self) -> String {
+        self.token_literal().to_string()
+    }
+}
+
+impl Node for BooleanLiteral {
+    fn token_literal(&self) -> &str {
+        token_text(&self.token)
+    }
+
+    fn as_string(&self) -> String {
+        self.value.to_string()
+    }
+}
+
+impl Node for FunctionLiteral {
+    fn token_literal(&self) -> &str {
+        token_text(&self.token)
+    }
+
+    fn as_string(&self) -> String {
+        let parameters = self
+            .parameters
+            .iter()
+            .map(|p| p.as_string())
+            .collect::<Vec<_>>();
+        format!(
+            "{}({}) {}",
+            self.token_literal(),
+            parameters.join(", "),
+            self.body.as_string()
+        )
+    }
+}
+
+impl Node for CallExpression {
+    fn token_literal(&self) -> &str {
+        token_text(&self.token)
+    }
+
+    fn as_string(&self) -> String {
+        let arguments = self
+            .arguments
+            .iter()
+            .map(|a| a.as_string())
+            .collect::<Vec<_>>();
+        format!("{}({})", self.function.as_string(), arguments.join(", "))
+    }
+}
+
+impl Node for PrefixExpression {
+    fn token_literal(&self) -> &str {
+        token_text(&self.token)
+    }
+
+    fn as_string(&self) -> String {
+        format!("({}{})", self.operator, self.right.as_string())
+    }
+}
+
+// Fully parenthesized, so `1 + 2 * 3` reads `(1 + (2 * 3))`.
+impl Node for InfixExpression {
+    fn token_literal(&self) -> &str {
+        token_text(&self.token)
+    }
+
+    fn as_string(&self) -> String {
+        format!(
+            "({} {} {})",
+            self.left.as_string(),
+            self.operator,
+            self.right.as_string()
+        )
+    }
+}
+
+impl Node for IfExpression {
+    fn token_literal(&self) -> &str {
+        token_text(&self.token)
+    }
+
+    fn as_string(&self) -> String {
+        let mut text = format!(
+            "{} {} {}",
+            self.token_literal(),
+            self.condition.as_string(),
+            self.consequence.as_string()
+        );
+        if let Some(alternative) = &self.alternative {
+            text.push_str(" else ");
+            text.push_str(&alternative.as_string());
+        }
+        text
+    }
+}
+
+impl Node for NullLiteral {
+    fn token_literal(&self) -> &str {
+        token_text(&self.token)
+    }
+
+    fn as_string(&self) -> String {
+        "null".to_string()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::lexer::Lexer;
+    use crate::parser::ast::{
+        Expression, ExpressionStatement, Identifier, IntegerLiteral, LetStatement, Node,
+        ReturnStatement, Statement,
+    };
+
+    #[test]
+    fn test_as_string() {
+        let tokens = Lexer::new().tokenize_all("let myVar = anotherVar; return 5");
+        let ident = |i: usize| Identifier {
+            token: tokens[i].clone(),
+            value: tokens[i].literal.clone().unwrap(),
+        };
+        let statements = [
+            Statement::Let(LetStatement {
+                token: tokens[0].clone(),
+                name: ident(1),
+                value: Expression::Identifier(ident(3)),
+            }),
+            Statement::Return(ReturnStatement {
+                token: tokens[5].clone(),
+                value: Expression::Integer(IntegerLiteral {
+                    token: tokens[6].clone(),
+                    value: 5,
+                }),
+            }),
+            Statement::Expression(ExpressionStatement {
+                token: tokens[1].clone(),
+                expression: Expression::Identifier(ident(1)),
+            }),
+        ];
+        let strings = statements.iter().map(Node::as_string).collect::<Vec<_>>();
+        assert_eq!(
+            vec!["let myVar = anotherVar;", "return 5;", "myVar"],
+            strings
+        );
+        let literals = statements
+            .iter()
+            .map(Node::token_literal)
+            .collect::<Vec<_>>();
+        assert_eq!(vec!["let", "return", "myVar"], literals);
+    }
+}
@@ -0,0 +1,725 @@
+pub mod ast;
+
+use crate::parser::ast::{
+    BlockStatement, BooleanLiteral, CallExpression, Expression, ExpressionStatement,
+    FunctionLiteral, Identifier, IfExpression, InfixExpression, IntegerLiteral, LetStatement, Node,
+    NullLiteral, PrefixExpression, ReturnStatement, Statement,
+};
+use crate::token::Keyword::{Else, False, Function, If, Let, Null, Return, True};
+use crate::token::Literal::{Ident, Int};
+use crate::token::Spec::{
+    Assign, Asterisk, Bang, Comma, Equal, Gt, Lbrace, Lparen, Lt, Minus, NotEqual, Plus, Rbrace,
+    Rparen, Semicolon, Slash,
+};
+use crate::token::{Token, TokenStream, TokenType};
+use std::error::Error;
+use std::fmt;
+
+#[derive(Debug, Clone, Default, Eq, PartialEq)]
+pub struct Program {
+    pub statements: Vec<Statement>,
+}
+
+impl Node for Program {
+    fn token_literal(&self) -> &str {
+        self.statements.first().map_or("", |s| s.token_literal())
+    }
+
+    fn as_string(&self) -> String {
+        self.statements.iter().map(|s| s.as_string()).collect()
+    }
+}
+
+/// Binding power of the operators, weakest first.
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Ord, PartialOrd)]
+pub enum Precedence {
+    Lowest = 1,
+    Equals,
+    LessGreater,
+    Sum,
+    Product,
+    Prefix,
+    Call,
+}
+
+impl Precedence {
+    fn of(token_type: TokenType) -> Precedence {
+        match token_type {
+            TokenType::Spec(Equal) | TokenType::Spec(NotEqual) => Precedence::Equals,
+            TokenType::Spec(Lt) | TokenType::Spec(Gt) => Precedence::LessGreater,
+            TokenType::Spec(Plus) | TokenType::Spec(Minus) => Precedence::Sum,
+            TokenType::Spec(Asterisk) | TokenType::Spec(Slash) => Precedence::Product,
+            TokenType::Spec(Lparen) => Precedence::Call,
+            _ => Precedence::Lowest,
+        }
+    }
+}
+
+/// A statement that could not be parsed; `line` and `col` are 0-based and point at the
+/// offending token.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct ParseError {
+    pub message: String,
+    pub line: usize,
+    pub col: usize,
+}
+
+pub struct Parser {
+    tokens: TokenStream,
+    pos: usize,
+    depth: usize,
+    errors: Vec<ParseError>,
+}
+
+impl Parser {
+    // Deeper expressions are rejected rather than risking a stack overflow.
+    const MAX_DEPTH: usize = 256;
+
+    pub fn new(tokens: Vec<Token>) -> Parser {
+        Parser {
+            tokens: TokenStream::new(tokens),
+            pos: 0,
+            depth: 0,
+            errors: Vec::new(),
+        }
+    }
+
+    /// Parses every statement up to `Eof`; statements that fail to parse are reported and
+    /// left out of the returned program.
+    pub fn parse(mut self) -> (Program, Vec<ParseError>) {
+        let mut program = Program::default();
+        while self.current_type() != TokenType::Eof {
+            match self.parse_statement() {
+                Some(statement) => program.statements.push(statement),
+                None => self.synchronize(false),
+            }
+        }
+        (program, self.errors)
+    }
+
+    fn parse_statement(&mut self) -> Option<Statement> {
+        match self.current_type() {
+            TokenType::Keyword(Let) => self.parse_let_statement(),
+            TokenType::Keyword(Return) => self.parse_return_statement(),
+            _ => self.parse_expression_statement(),
+        }
+    }
+
+    fn parse_let_statement(&mut self) -> Option<Statement> {
+        let token = self.expect(TokenType::Keyword(Let))?;
+        let name = self.parse_identifier()?;
+        self.expect(TokenType::Spec(Assign))?;
+        let value = self.parse_expression(Precedence::Lowest)?;
+        self.expect(TokenType::Spec(Semicolon))?;
+        Some(Statement::Let(LetStatement { token, name, value }))
+    }
+
+    fn parse_return_statement(&mut self) -> Option<Statement> {
+        let token = self.expect(TokenType::Keyword(Return))?;
+        let value = match self.current() {
+            Some(semicolon) if semicolon.token_type == TokenType::Spec(Semicolon) => {
+                let token = semicolon.clone();
+                Expression::Null(NullLiteral { token })
+            }
+            _ => self.parse_expression(Precedence::Lowest)?,
+        };
+        self.expect(TokenType::Spec(Semicolon))?;
+        Some(Statement::Return(ReturnStatement { token, value }))
+    }
+
+    // The `;` after an expression statement is optional, so `{ x }` is a valid block.
+    fn parse_expression_statement(&mut self) -> Option<Statement> {
+        let token = self.current()?.clone();
+        let expression = self.parse_expression(Precedence::Lowest)?;
+        if self.current_type() == TokenType::Spec(Semicolon) {
+            self.advance();
+        }
+        Some(Statement::Expression(ExpressionStatement {
+            token,
+            expression,
+        }))
+    }
+
+    fn parse_block_statement(&mut self) -> Option<BlockStatement> {
+        let token = self.expect(TokenType::Spec(Lbrace))?;
+        let mut statements = Vec::new();
+        loop {
+            match self.current_type() {
+                TokenType::Spec(Rbrace) => break self.advance(),
+                TokenType::Eof => {
+                    self.error("expected }, found <eof>".to_string());
+                    return None;
+                }
+                _ => match self.parse_statement() {
+                    Some(statement) => statements.push(statement),
+                    None => self.synchronize(true),
+                },
+            }
+        }
+        Some(BlockStatement { token, statements })
+    }
+
+    fn parse_expression(&mut self, precedence: Precedence) -> Option<Expression> {
+        if self.depth == Parser::MAX_DEPTH {
+            self.error("expression nested too deeply".to_string());
+            return None;
+        }
+        self.depth += 1;
+        let expression = self.parse_nested_expression(precedence);
+        self.depth -= 1;
+        expression
+    }
+
+    // Operators binding tighter than `precedence` are folded into the left operand.
+    fn parse_nested_expression(&mut self, precedence: Precedence) -> Option<Expression> {
+        let mut left = self.parse_primary_expression()?;
+        while precedence < Precedence::of(self.current_type()) {
+            left = self.parse_infix_expression(left)?;
+        }
+        Some(left)
+    }
+
+    fn parse_infix_expression(&mut self, left: Expression) -> Option<Expression> {
+        let token_type = self.current_type();
+        if token_type == TokenType::Spec(Lparen) {
+            return self.parse_call_expression(left);
+        }
+        let token = self.expect(token_type)?;
+        let operator = token_type.to_string();
+        let right = self.parse_expression(Precedence::of(token_type))?;
+        Some(Expression::Infix(InfixExpression {
+            token,
+            left: Box::new(left),
+            operator,
+            right: Box::new(right),
+        }))
+    }
+
+    fn parse_primary_expression(&mut self) -> Option<Expression> {
+        match self.current_type() {
+            TokenType::Literal(Ident) => self.parse_identifier().map(Expression::Identifier),
+            TokenType::Literal(Int) => self.parse_integer_literal(),
+            TokenType::Keyword(True) | TokenType::Keyword(False) => self.parse_boolean_literal(),
+            TokenType::Keyword(Function) => self.parse_function_literal(),
+            TokenType::Spec(Bang) | TokenType::Spec(Minus) => self.parse_prefix_expression(),
+            TokenType::Spec(Lparen) => self.parse_grouped_expression(),
+            TokenType::Keyword(If) => self.parse_if_expression(),
+            TokenType::Keyword(Null) => {
+                let token = self.expect(TokenType::Keyword(Null))?;
+                Some(Expression::Null(NullLiteral { token }))
+            }
+            other => {
+                self.error(format!("unexpected token {}", other));
+                None
+            }
+        }
+    }
+
+    fn parse_identifier(&mut self) -> Option<Identifier> {
+        let token = self.expect(TokenType::Literal(Ident))?;
+        let value = token.literal.clone().unwrap_or_default();
+        Some(Identifier { token, value })
+    }
+
+    fn parse_integer_literal(&mut self) -> Option<Expression> {
+        let value = match self.current()?.try_parse_int() {
+            Some(value) => value,
+            None => {
+                self.error("integer literal out of range".to_string());
+                return None;
+            }
+        };
+        let token = self.expect(TokenType::Literal(Int))?;
+        Some(Expression::Integer(IntegerLiteral { token, value }))
+    }
+
+    fn parse_boolean_literal(&mut self) -> Option<Expression> {
+        let value = self.current_type() == TokenType::Keyword(True);
+        let token = self.expect(self.current_type())?;
+        Some(Expression::Boolean(BooleanLiteral { token, value }))
+    }
+
+    fn parse_prefix_expression(&mut self) -> Option<Expression> {
+        let token_type = self.current_type();
+        let token = self.expect(token_type)?;
+        let operator = token_type.to_string();
+        let right = self.parse_expression(Precedence::Prefix)?;
+        Some(Expression::Prefix(PrefixExpression {
+            token,
+            operator,
+            right: Box::new(right),
+        }))
+    }
+
+    fn parse_grouped_expression(&mut self) -> Option<Expression> {
+        self.expect(TokenType::Spec(Lparen))?;
+        let expression = self.parse_expression(Precedence::Lowest)?;
+        self.expect(TokenType::Spec(Rparen))?;
+        Some(expression)
+    }
+
+    fn parse_if_expression(&mut self) -> Option<Expression> {
+        let token = self.expect(TokenType::Keyword(If))?;
+        self.expect(TokenType::Spec(Lparen))?;
+        let condition = self.parse_expression(Precedence::Lowest)?;
+        self.expect(TokenType::Spec(Rparen))?;
+        let consequence = self.parse_block_statement()?;
+        let alternative = match self.current_type() {
+            TokenType::Keyword(Else) => {
+                self.advance();
+                Some(self.parse_block_statement()?)
+            }
+            _ => None,
+        };
+        Some(Expression::If(IfExpression {
+            token,
+            condition: Box::new(condition),
+            consequence,
+            alternative,
+        }))
+    }
+
+    fn parse_function_literal(&mut self) -> Option<Expression> {
+        let token = self.expect(TokenType::Keyword(Function))?;
+        self.expect(TokenType::Spec(Lparen))?;
+        let mut parameters = Vec::new();
+        if self.current_type() != TokenType::Spec(Rparen) {
+            parameters.push(self.parse_identifier()?);
+            while self.current_type() == TokenType::Spec(Comma) {
+                self.advance();
+                parameters.push(self.parse_identifier()?);
+            }
+        }
+        self.expect(TokenType::Spec(Rparen))?;
+        let body = self.parse_block_statement()?;
+        Some(Expression::Function(FunctionLiteral {
+            token,
+            parameters,
+            body,
+        }))
+    }
+
+    fn parse_call_expression(&mut self, function: Expression) -> Option<Expression> {
+        let token = self.expect(TokenType::Spec(Lparen))?;
+        let mut arguments = Vec::new();
+        if self.current_type() != TokenType::Spec(Rparen) {
+            arguments.push(self.parse_expression(Precedence::Lowest)?);
+            while self.current_type() == TokenType::Spec(Comma) {
+                self.advance();
+                arguments.push(self.parse_expression(Precedence::Lowest)?);
+            }
+        }
+        self.expect(TokenType::Spec(Rparen))?;
+        Some(Expression::Call(CallExpression {
+            token,
+            function: Box::new(function),
+            arguments,
+        }))
+    }
+
+    fn current(&self) -> Option<&Token> {
+        self.tokens.tokens().get(self.pos)
+    }
+
+    // Consumes the current token if it has the expected type, otherwise reports it.
+    fn expect(&mut self, expected: TokenType) -> Option<Token> {
+        match self.current() {
+            Some(token) if token.token_type == expected => {
+                let token = token.clone();
+                self.advance();
+                Some(token)
+            }
+            _ => {
+                let message = format!("expected {}, found {}", expected, self.current_type());
+                self.error(message);
+                None
+            }
+        }
+    }
+
+    // The token at `pos`; a stream that ends without `Eof` behaves as if it had one.
+    fn current_type(&self) -> TokenType {
+        self.current()
+            .map_or(TokenType::Eof, |token| token.token_type)
+    }
+
+    fn advance(&mut self) {
+        if self.pos < self.tokens.len() {
+            self.pos += 1;
+        }
+    }
+
+    // Skips the rest of a broken statement, up to and including its `;`, or up to the
+    // `let` or `return` starting the next one. Inside a block it also stops at the `}`
+    // closing the block, leaving it for `parse_block_statement`.
+    fn synchronize(&mut self, in_block: bool) {
+        loop {
+            match self.current_type() {
+                TokenType::Eof | TokenType::Keyword(Let) | TokenType::Keyword(Return) => return,
+                TokenType::Spec(Rbrace) if in_block => return,
+                TokenType::Spec(Semicolon) => return self.advance(),
+                _ => self.advance(),
+            }
+        }
+    }
+
+    fn error(&mut self, message: String) {
+        let tokens = self.tokens.tokens();
+        let (line, col) = tokens
+            .get(self.pos)
+            .or_else(|| tokens.last())
+            .map_or((0, 0), |token| (token.line, token.col));
+        self.errors.push(ParseError { message, line, col });
+    }
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "parse error at line {}, col {}: {}",
+            self.line + 1,
+            self.col + 1,
+            self.message
+        )
+    }
+}
+
+impl Error for ParseError {}
+
+#[cfg(test)]
+mod test {
+    use crate::lexer::Lexer;
+    use crate::parser::ast::{
+        Expression, ExpressionStatement, IntegerLiteral, LetStatement, Node, ReturnStatement,
+        Statement,
+    };
+    use crate::parser::{ParseError, Parser, Program};
+
+    fn parse(code: &str) -> (Program, Vec<ParseError>) {
+        Parser::new(Lexer::new().tokenize_all(code)).parse()
+    }
+
+    #[test]
+    fn test_parse_empty() {
+        assert_eq!((Program::default(), vec![]), parse(""));
+        assert_eq!((Program::default(), vec![]), parse("  // nothing\n"));
+        assert_eq!((Program::default(), vec![]), Parser::new(vec![]).parse());
+    }
+
+    #[test]
+    fn test_parse_recovers_after_error() {
+        let (program, errors) = parse("let; 0x; let y = 1;");
+        assert_eq!("let y = 1;", program.as_string());
+        let messages = errors.iter().map(|e| e.to_string()).collect::<Vec<_>>();
+        assert_eq!(
+            vec![
+                "parse error at line 1, col 4: expected <ident>, found ;",
+                "parse error at line 1, col 6: unexpected token <illegal>",
+            ],
+            messages
+        );
+
+        let tokens = Lexer::new().tokenize_valid("let x");
+        let (_, errors) = Parser::new(tokens[..2].to_vec()).parse();
+        let error = ParseError {
+            message: "expected =, found <eof>".to_string(),
+            line: 0,
+            col: 4,
+        };
+        assert_eq!(vec![error], errors);
+    }
+
+    #[test]
+    fn test_let_statement() {
+        let (program, errors) = parse("let x = 5;\nlet answer = y;");
+        assert_eq!(Vec::<ParseError>::new(), errors);
+        let names = program
+            .statements
+            .iter()
+            .map(|statement| match statement {
+                Statement::Let(LetStatement { name, .. }) => name.value.as_str(),
+                _ => panic!("not a let statement: {:?}", statement),
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(vec!["x", "answer"], names);
+        assert_eq!("let x = 5;let answer = y;", program.as_string());
+        let value = match &program.statements[0] {
+            Statement::Let(LetStatement { value, .. }) => value,
+            _ => unreachable!(),
+        };
+        assert!(matches!(
+            value,
+            Expression::Integer(IntegerLiteral { value: 5, .. })
+        ));
+    }
+
+    #[test]
+    fn test_let_function() {
+        let (program, errors) = parse("let add = fn(x, y) { x + y; };");
+        assert_eq!(Vec::<ParseError>::new(), errors);
+        assert_eq!("let add = fn(x, y) { (x + y) };", program.as_string());
+        match &program.statements[..] {
+            [Statement::Let(LetStatement {
+                value: Expression::Function(function),
+                ..
+            })] => {
+                assert_eq!(2, function.parameters.len());
+                assert_eq!(1, function.body.statements.len());
+            }
+            statements => panic!("not a function: {:?}", statements),
+        }
+        assert_eq!("let f = fn() {};", parse("let f = fn() {};").0.as_string());
+    }
+
+    #[test]
+    fn test_let_statement_errors() {
+        let (program, errors) = parse("let x 5;\nlet = 10;\nlet y = 1;\nlet z = 2");
+        assert_eq!("let y = 1;", program.as_string());
+        let messages = errors
+            .iter()
+            .map(|e| (e.line, e.col, e.message.as_str()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            vec![
+                (0, 6, "expected =, found <int>"),
+                (1, 4, "expected <ident>, found ="),
+                (3, 9, "expected ;, found <eof>"),
+            ],
+            messages
+        );
+    }
+
+    #[test]
+    fn test_return_statement() {
+        let (program, errors) = parse("return 5;\nreturn add(x, y);\nreturn;\nreturn f()(1);");
+        assert_eq!(Vec::<ParseError>::new(), errors);
+        let values = program
+            .statements
+            .iter()
+            .map(|statement| match statement {
+                Statement::Return(ReturnStatement { value, .. }) => value.as_string(),
+                _ => panic!("not a return statement: {:?}", statement),
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(vec!["5", "add(x, y)", "null", "f()(1)"], values);
+        match &program.statements[2] {
+            Statement::Return(ReturnStatement { value, .. }) => {
+                assert!(matches!(value, Expression::Null(_)))
+            }
+            _ => unreachable!(),
+        }
+    }
+
+    #[test]
+    fn test_return_statement_errors() {
+        let (program, errors) = parse("return 5\nlet x = 1;\nreturn add(x;\nreturn x;");
+        assert_eq!("let x = 1;return x;", program.as_string());
+        let messages = errors
+            .iter()
+            .map(|e| (e.line, e.col, e.message.as_str()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            vec![
+                (1, 0, "expected ;, found let"),
+                (2, 12, "expected ), found ;"),
+            ],
+            messages
+        );
+    }
+
+    #[test]
+    fn test_operator_precedence() {
+        let tests = [
+            ("1 + 2 * 3", "(1 + (2 * 3))"),
+            ("1 * 2 + 3", "((1 * 2) + 3)"),
+            ("1 - 2 / 3 * 4", "(1 - ((2 / 3) * 4))"),
+            ("a + b(c) * d", "(a + (b(c) * d))"),
+            ("f(1 + 2, g(3) * 4)", "f((1 + 2), (g(3) * 4))"),
+            ("-a * b", "((-a) * b)"),
+            ("a + -b", "(a + (-b))"),
+            ("!f(x)", "(!f(x))"),
+        ];
+        for (code, expected) in tests.iter() {
+            let (program, errors) = parse(code);
+            assert_eq!(Vec::<ParseError>::new(), errors, "{}", code);
+            assert_eq!(*expected, program.as_string());
+        }
+    }
+
+    #[test]
+    fn test_prefix_expression() {
+        let tests = [
+            ("!true", "(!true)"),
+            ("-5", "(-5)"),
+            ("!!false", "(!(!false))"),
+            ("!-5", "(!(-5))"),
+            ("--x", "(-(-x))"),
+        ];
+        for (code, expected) in tests.iter() {
+            let (program, errors) = parse(code);
+            assert_eq!(Vec::<ParseError>::new(), errors, "{}", code);
+            assert_eq!(*expected, program.as_string());
+        }
+
+        let (program, _) = parse("-5");
+        match &program.statements[..] {
+            [Statement::Expression(ExpressionStatement {
+                expression: Expression::Prefix(prefix),
+                ..
+            })] => {
+                assert_eq!("-", prefix.operator);
+                assert!(matches!(
+                    *prefix.right,
+                    Expression::Integer(IntegerLiteral { value: 5, .. })
+                ));
+            }
+            statements => panic!("not a prefix expression: {:?}", statements),
+        }
+
+        let (_, errors) = parse("!;");
+        assert_eq!("unexpected token ;", errors[0].message);
+    }
+
+    #[test]
+    fn test_infix_expression() {
+        let tests = [
+            ("a + b + c", "((a + b) + c)"),
+            ("a - b - c", "((a - b) - c)"),
+            ("a * b / c", "((a * b) / c)"),
+            ("a + b * c", "(a + (b * c))"),
+            ("a + b / c - d", "((a + (b / c)) - d)"),
+            ("5 > 4 == 3 < 4", "((5 > 4) == (3 < 4))"),
+            ("5 < 4 != 3 > 4", "((5 < 4) != (3 > 4))"),
+            (
+                "3 + 4 * 5 == 3 * 1 + 4 * 5",
+                "((3 + (4 * 5)) == ((3 * 1) + (4 * 5)))",
+            ),
+            ("true != false == x < -1", "((true != false) == (x < (-1)))"),
+        ];
+        for (code, expected) in tests.iter() {
+            let (program, errors) = parse(code);
+            assert_eq!(Vec::<ParseError>::new(), errors, "{}", code);
+            assert_eq!(*expected, program.as_string());
+        }
+
+        let (program, _) = parse("1 != 2");
+        match &program.statements[..] {
+            [Statement::Expression(ExpressionStatement {
+                expression: Expression::Infix(infix),
+                ..
+            })] => {
+                assert_eq!("!=", infix.operator);
+                assert_eq!("1", infix.left.as_string());
+                assert_eq!("2", infix.right.as_string());
+            }
+            statements => panic!("not an infix expression: {:?}", statements),
+        }
+
+        let (_, errors) = parse("1 + ;");
+        assert_eq!("unexpected token ;", errors[0].message);
+    }
+
+    #[test]
+    fn test_grouped_expression() {
+        let tests = [
+            ("(5)", "5"),
+            ("((((5))))", "5"),
+            ("(1 + 2) * 3", "((1 + 2) * 3)"),
+            ("2 / (5 + 5)", "(2 / (5 + 5))"),
+            ("-(-5)", "(-(-5))"),
+            ("!(true == false)", "(!(true == false))"),
+            ("(f)(1)", "f(1)"),
+        ];
+        for (code, expected) in tests.iter() {
+            let (program, errors) = parse(code);
+            assert_eq!(Vec::<ParseError>::new(), errors, "{}", code);
+            assert_eq!(*expected, program.as_string());
+        }
+
+        let (program, errors) = parse("(1 + 2;\nlet x = 1;");
+        assert_eq!("let x = 1;", program.as_string());
+        assert_eq!((0, 6), (errors[0].line, errors[0].col));
+        assert_eq!("expected ), found ;", errors[0].message);
+        let (_, errors) = parse("((5)");
+        assert_eq!("expected ), found <eof>", errors[0].message);
+    }
+
+    #[test]
+    fn test_nesting_limit() {
+        let nested = |open: &str, close: &str, depth| {
+            format!("{}1{}; let y = 2;", open.repeat(depth), close.repeat(depth))
+        };
+        let (program, errors) = parse(&nested("(", ")", 100));
+        assert_eq!(Vec::<ParseError>::new(), errors);
+        assert_eq!("1let y = 2;", program.as_string());
+
+        for code in [nested("(", ")", 100_000), nested("-", "", 100_000)].iter() {
+            let (program, errors) = parse(code);
+            assert_eq!("let y = 2;", program.as_string());
+            let messages = errors.into_iter().map(|e| e.message).collect::<Vec<_>>();
+            assert_eq!(vec!["expression nested too deeply"], messages);
+        }
+    }
+
+    #[test]
+    fn test_if_expression() {
+        let (program, errors) = parse("if (x > 5) { x }");
+        assert_eq!(Vec::<ParseError>::new(), errors);
+        match &program.statements[..] {
+            [Statement::Expression(ExpressionStatement {
+                expression: Expression::If(expression),
+                ..
+            })] => {
+                assert_eq!("(x > 5)", expression.condition.as_string());
+                assert_eq!("{ x }", expression.consequence.as_string());
+                assert_eq!(None, expression.alternative);
+            }
+            statements => panic!("not an if expression: {:?}", statements),
+        }
+
+        let tests = [
+            ("if (true) { 1 } else { 2 }", "if true { 1 } else { 2 }"),
+            (
+                "let y = if (x) { 1; } else { };",
+                "let y = if x { 1 } else {};",
+            ),
+            (
+                "if (a) { if (b) { c } } else { d }",
+                "if a { if b { c } } else { d }",
+            ),
+        ];
+        for (code, expected) in tests.iter() {
+            let (program, errors) = parse(code);
+            assert_eq!(Vec::<ParseError>::new(), errors, "{}", code);
+            assert_eq!(*expected, program.as_string());
+        }
+    }
+
+    #[test]
+    fn test_if_expression_errors() {
+        let messages = |code| {
+            let (_, errors) = parse(code);
+            errors.into_iter().map(|e| e.message).collect::<Vec<_>>()
+        };
+        assert_eq!(
+            vec!["expected (, found <ident>"],
+            messages("if x > 5 { x }")
+        );
+        assert_eq!(vec!["expected ), found {"], messages("if (x { x }"));
+        assert_eq!(vec!["expected {, found <ident>"], messages("if (x) x"));
+        assert_eq!(
+            vec!["expected {, found <eof>"],
+            messages("if (x) { 1 } else")
+        );
+        assert_eq!(vec!["expected }, found <eof>"], messages("if (x) { 1"));
+    }
+
+    #[test]
+    fn test_block_recovers_after_error() {
+        let (program, errors) = parse("if (x) { 1 + } let y = 2;");
+        assert_eq!("if x {}let y = 2;", program.as_string());
+        let messages = errors.into_iter().map(|e| e.message).collect::<Vec<_>>();
+        assert_eq!(vec!["unexpected token }"], messages);
+    }
+}
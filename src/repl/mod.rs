@@ -1,30 +1,425 @@
 use std::io;
 use std::io::*;
 use crate::lexer::Lexer;
+use crate::token::Spec::{Lbrace, Lparen, Rbrace, Rparen};
+use crate::token::{Token, TokenType};
+use std::error::Error;
+use std::fs;
+use std::path::Path;
 
-pub struct Repl;
+pub struct Repl {
+    prompt: String,
+    continuation_prompt: String,
+    #[cfg(feature = "colors")]
+    colors: bool,
+}
+
+impl Default for Repl {
+    fn default() -> Self {
+        Repl::new()
+    }
+}
 
 impl Repl {
     const PROMT: &'static str = "λ >> ";
+    const HELP: &'static str = "\
+pomidor is a small Monkey-like language; each line you enter is printed as tokens.
+
+Commands:
+  :tokens <code>  print the tokens of <code>, one per line
+  :help           show this message
+  :quit, :exit    leave the REPL
+";
+
+    pub fn new() -> Repl {
+        Repl::with_prompt(Repl::PROMT)
+    }
+
+    /// Uses `prompt` for new input; lines continuing unclosed input are prompted with
+    /// the visible part of `prompt` blanked out and followed by `...`.
+    pub fn with_prompt(prompt: impl Into<String>) -> Repl {
+        let prompt = prompt.into();
+        let visible = prompt.trim_end();
+        let continuation_prompt = format!(
+            "{}...{}",
+            " ".repeat(visible.chars().count()),
+            &prompt[visible.len()..]
+        );
+        Repl {
+            prompt,
+            continuation_prompt,
+            #[cfg(feature = "colors")]
+            colors: false,
+        }
+    }
 
-    pub const fn new() -> Repl {
-        Repl
+    #[cfg(not(feature = "rustyline"))]
+    pub fn run(&mut self) -> std::result::Result<(), Box<dyn Error>> {
+        #[cfg(feature = "colors")]
+        self.enable_colors_on_terminal();
+        let stdin = io::stdin();
+        let stdout = io::stdout();
+        self.run_with_io(stdin.lock(), &mut stdout.lock())?;
+        Ok(())
+    }
+
+    /// Reads lines with `rustyline`, keeping the history in `~/.pomidor_history`.
+    #[cfg(feature = "rustyline")]
+    pub fn run(&mut self) -> std::result::Result<(), Box<dyn Error>> {
+        use rustyline::error::ReadlineError;
+
+        #[cfg(feature = "colors")]
+        self.enable_colors_on_terminal();
+        let lexer = Lexer::new();
+        let mut editor = rustyline::DefaultEditor::new()?;
+        let history = std::env::var_os("HOME")
+            .map(|home| std::path::Path::new(&home).join(".pomidor_history"));
+        if let Some(history) = &history {
+            let _ = editor.load_history(history);
+        }
+        let mut stdout = io::stdout();
+        let mut buffer = String::new();
+        let result = loop {
+            let prompt = match buffer.is_empty() {
+                true => &self.prompt,
+                false => &self.continuation_prompt,
+            };
+            match editor.readline(prompt) {
+                Ok(line) => {
+                    let _ = editor.add_history_entry(line.as_str());
+                    buffer.push_str(&line);
+                    buffer.push('\n');
+                    match self.submit(&lexer, &mut buffer, &mut stdout) {
+                        Ok(Some(_)) => {}
+                        Ok(None) => break Ok(()),
+                        Err(err) => break Err(err.into()),
+                    }
+                }
+                Err(ReadlineError::Interrupted) | Err(ReadlineError::Eof) => break Ok(()),
+                Err(err) => break Err(err.into()),
+            }
+        };
+        if let Some(history) = &history {
+            let _ = editor.save_history(history);
+        }
+        result
+    }
+
+    /// Prints the tokens of the script at `path`.
+    pub fn run_file(&self, path: &Path) -> std::result::Result<(), Box<dyn Error>> {
+        let source = fs::read_to_string(path)?;
+        let mut stdout = io::stdout();
+        for token in Lexer::new().tokenize_all(&source) {
+            writeln!(stdout, "{:?}", token)?;
+        }
+        Ok(())
     }
 
-    pub fn run(&mut self) {
+    /// Runs the read-tokenize-print loop until `input` is exhausted.
+    pub fn run_with_io<R: BufRead, W: Write>(
+        &mut self,
+        mut input: R,
+        output: &mut W,
+    ) -> io::Result<()> {
         let lexer = Lexer::new();
+        let mut buffer = String::new();
+        let mut prompt = self.prompt.as_str();
         loop {
-            print!("{}", Repl::PROMT);
-            let mut buffer = String::new();
+            write!(output, "{}", prompt)?;
+            output.flush()?;
+            if input.read_line(&mut buffer)? == 0 {
+                if !buffer.is_empty() {
+                    self.print_tokens(&lexer, &buffer, output)?;
+                }
+                return Ok(());
+            }
 
-            let stdin = io::stdin();
-            let mut stdout = io::stdout();
+            match self.submit(&lexer, &mut buffer, output)? {
+                Some(next) => prompt = next,
+                None => return Ok(()),
+            }
+        }
+    }
+
+    // Handles the buffered input after a line was read and returns the prompt for the
+    // next one, or `None` once the user quits.
+    fn submit<W: Write>(
+        &self,
+        lexer: &Lexer,
+        buffer: &mut String,
+        output: &mut W,
+    ) -> io::Result<Option<&str>> {
+        match Command::parse(buffer) {
+            Some(Command::Quit) => {
+                output.flush()?;
+                return Ok(None);
+            }
+            Some(Command::Help) => write!(output, "{}", Repl::HELP)?,
+            Some(Command::Tokens(code)) => {
+                for token in lexer.tokenize_lossy(code) {
+                    writeln!(output, "{}", token)?;
+                }
+            }
+            None if !Repl::is_complete(lexer, buffer) => {
+                return Ok(Some(&self.continuation_prompt))
+            }
+            None => self.print_tokens(lexer, buffer, output)?,
+        }
+        buffer.clear();
+        Ok(Some(&self.prompt))
+    }
+
+    // Input is submitted once every `{` and `(` in it has been closed.
+    fn is_complete(lexer: &Lexer, code: &str) -> bool {
+        let tokens = lexer.tokenize_lossy(code).collect::<Vec<_>>();
+        count_open_delimiters(&tokens) <= 0
+    }
+
+    fn print_tokens<W: Write>(&self, lexer: &Lexer, code: &str, output: &mut W) -> io::Result<()> {
+        #[cfg(feature = "colors")]
+        if self.colors {
+            return print_colored_tokens(lexer, code, &mut termcolor::Ansi::new(output));
+        }
+        for item in lexer.tokenize(code) {
+            match item {
+                Ok(token) => writeln!(output, "{:?}", token)?,
+                Err(err) => writeln!(output, "{}", err)?,
+            }
+        }
+        Ok(())
+    }
+
+    #[cfg(feature = "colors")]
+    fn enable_colors_on_terminal(&mut self) {
+        self.colors = io::stdout().is_terminal();
+    }
+}
 
-            stdout.flush().unwrap();
-            stdin.read_line(&mut buffer).unwrap();
+#[cfg(feature = "colors")]
+fn print_colored_tokens<W: termcolor::WriteColor>(
+    lexer: &Lexer,
+    code: &str,
+    output: &mut W,
+) -> io::Result<()> {
+    use crate::token::Literal::{Bin, Char, Float, Hex, Int, Oct, Str};
+    use termcolor::{Color, ColorSpec};
 
-            lexer.tokenize(&buffer).for_each(|token| println!("{:?}", token));
-            buffer.clear();
+    for item in lexer.tokenize(code) {
+        let mut spec = ColorSpec::new();
+        match &item {
+            Ok(token) => match token.token_type {
+                TokenType::Keyword(_) => spec.set_fg(Some(Color::Blue)).set_bold(true),
+                TokenType::Literal(Int)
+                | TokenType::Literal(Float)
+                | TokenType::Literal(Hex)
+                | TokenType::Literal(Bin)
+                | TokenType::Literal(Oct) => spec.set_fg(Some(Color::Green)),
+                TokenType::Literal(Str) | TokenType::Literal(Char) => {
+                    spec.set_fg(Some(Color::Yellow))
+                }
+                TokenType::Spec(_) => spec.set_fg(Some(Color::White)),
+                _ => &mut spec,
+            },
+            Err(_) => spec.set_fg(Some(Color::Red)).set_bold(true),
+        };
+        output.set_color(&spec)?;
+        match item {
+            Ok(token) => write!(output, "{:?}", token)?,
+            Err(err) => write!(output, "{}", err)?,
         }
+        output.reset()?;
+        writeln!(output)?;
+    }
+    Ok(())
+}
+
+enum Command<'a> {
+    Quit,
+    Help,
+    Tokens(&'a str),
+}
+
+impl<'a> Command<'a> {
+    fn parse(line: &'a str) -> Option<Command<'a>> {
+        let line = line.trim();
+        let (name, rest) = line.split_at(line.find(char::is_whitespace).unwrap_or(line.len()));
+        match name {
+            ":quit" | ":exit" => Some(Command::Quit),
+            ":help" => Some(Command::Help),
+            ":tokens" => Some(Command::Tokens(rest.trim_start())),
+            _ => None,
+        }
+    }
+}
+
+fn count_open_delimiters(tokens: &[Token]) -> i32 {
+    tokens
+        .iter()
+        .map(|token| match token.token_type {
+            TokenType::Spec(Lbrace) | TokenType::Spec(Lparen) => 1,
+            TokenType::Spec(Rbrace) | TokenType::Spec(Rparen) => -1,
+            _ => 0,
+        })
+        .sum()
+}
+
+#[cfg(test)]
+mod test {
+    use crate::lexer::Lexer;
+    use crate::repl::{count_open_delimiters, Repl};
+    use std::env;
+    use std::fs;
+    use std::io::Cursor;
+
+    fn run(input: &str) -> String {
+        let mut output = Vec::new();
+        Repl::new()
+            .run_with_io(Cursor::new(input.as_bytes()), &mut output)
+            .unwrap();
+        String::from_utf8(output).unwrap()
+    }
+
+    #[test]
+    fn test_run_with_io() {
+        let expected = [
+            "λ >> Token { token_type: Keyword(Let), literal: None, line: 0, col: 0, span: SourceSpan { source_id: 0, start: 0, end: 3 } }",
+            "Token { token_type: Literal(Ident), literal: Some(\"x\"), line: 0, col: 4, span: SourceSpan { source_id: 0, start: 4, end: 5 } }",
+            "Token { token_type: Eof, literal: None, line: 1, col: 0, span: SourceSpan { source_id: 0, start: 6, end: 6 } }",
+            "λ >> unexpected character '#' at 1:1",
+            "Token { token_type: Eof, literal: None, line: 1, col: 0, span: SourceSpan { source_id: 0, start: 2, end: 2 } }",
+            "λ >> ",
+        ];
+        assert_eq!(expected.join("\n"), run("let x\n#\n"));
+    }
+
+    #[test]
+    fn test_run_with_io_empty_input() {
+        assert_eq!("λ >> ", run(""));
+    }
+
+    #[test]
+    fn test_run_with_io_errors() {
+        let mut output = Vec::new();
+        let error = Repl::new()
+            .run_with_io(Cursor::new(&b"let \xff\n"[..]), &mut output)
+            .unwrap_err();
+        assert_eq!(std::io::ErrorKind::InvalidData, error.kind());
+
+        let mut output = [0u8; 2];
+        let error = Repl::new()
+            .run_with_io(Cursor::new("let x\n".as_bytes()), &mut &mut output[..])
+            .unwrap_err();
+        assert_eq!(std::io::ErrorKind::WriteZero, error.kind());
+    }
+
+    #[test]
+    fn test_count_open_delimiters() {
+        let lexer = Lexer::new();
+        let count = |code| count_open_delimiters(&lexer.tokenize_lossy(code).collect::<Vec<_>>());
+        assert_eq!(0, count("let x = 5;"));
+        assert_eq!(2, count("fn(x) { if (x"));
+        assert_eq!(1, count("{ [1, 2] }}{ {"));
+        assert_eq!(-1, count(")"));
+        assert_eq!(0, count("\"{(\" // {"));
+    }
+
+    #[test]
+    fn test_run_with_io_multi_line() {
+        let output = run("fn(x) {\n x + 1;\n}\n");
+        assert!(output.starts_with("λ >>     ...     ... Token { token_type: Keyword(Function)"));
+        let lines = output.lines().collect::<Vec<_>>();
+        assert_eq!(12, lines.len());
+        assert!(lines[10].starts_with("Token { token_type: Eof, literal: None, line: 3, col: 0"));
+        assert_eq!("λ >> ", lines[11]);
+    }
+
+    #[test]
+    fn test_run_with_io_unclosed_at_eof() {
+        let output = run("{\n");
+        assert!(output.starts_with("λ >>     ... Token { token_type: Spec(Lbrace)"));
+        assert_eq!(2, output.lines().count());
+    }
+
+    #[test]
+    fn test_quit() {
+        assert_eq!("λ >> ", run(":quit\nlet x\n"));
+        assert_eq!("λ >> ", run("  :exit  \nlet x\n"));
+        assert_eq!(2, run("\n:quit\nlet x\n").matches(Repl::PROMT).count());
+    }
+
+    #[test]
+    fn test_tokens() {
+        let expected = [
+            "λ >> let",
+            "x",
+            "=",
+            "a",
+            "+",
+            "1",
+            ";",
+            "<eof>",
+            "λ >> <illegal>",
+            "<eof>",
+            "λ >> <eof>",
+            "λ >> ",
+        ];
+        let input = ":tokens let x = \"a\" + 1;\n:tokens #\n:tokens\n";
+        assert_eq!(expected.join("\n"), run(input));
+        assert_eq!("λ >> fn\n(\n<eof>\nλ >> ", run(":tokens fn(\n"));
+    }
+
+    #[test]
+    fn test_help() {
+        let output = run(":help\n:quit\n");
+        assert!(output.starts_with("λ >> pomidor is"));
+        assert!(output.contains(":quit"));
+        assert!(output.contains(":tokens"));
+        assert!(output.ends_with("\nλ >> "));
+    }
+
+    #[cfg(feature = "colors")]
+    #[test]
+    fn test_colors() {
+        let mut output = Vec::new();
+        let mut repl = Repl::new();
+        repl.colors = true;
+        repl.run_with_io(Cursor::new("let # 5".as_bytes()), &mut output)
+            .unwrap();
+        let output = String::from_utf8(output).unwrap();
+        let lines = output.lines().collect::<Vec<_>>();
+        assert!(lines[0].starts_with("λ >> \x1b[0m\x1b[1m\x1b[34mToken { token_type: Keyword(Let)"));
+        assert!(lines[0].ends_with("}\x1b[0m"));
+        assert!(lines[1].starts_with("\x1b[0m\x1b[1m\x1b[31munexpected character '#'"));
+    }
+
+    #[test]
+    fn test_run_file() {
+        let path = env::temp_dir().join(format!("pomidor-run-file-{}.monkey", std::process::id()));
+        fs::write(&path, "let x = 5;\n").unwrap();
+        let result = Repl::new().run_file(&path);
+        fs::remove_file(&path).unwrap();
+        assert!(result.is_ok());
+
+        let error = Repl::new().run_file(&path).unwrap_err();
+        assert_eq!(
+            Some(std::io::ErrorKind::NotFound),
+            error.downcast_ref::<std::io::Error>().map(|e| e.kind())
+        );
+    }
+
+    #[test]
+    fn test_with_prompt() {
+        let mut output = Vec::new();
+        let mut repl = Repl::with_prompt("pomidor> ");
+        repl.run_with_io(Cursor::new(":help\n(\n)\n".as_bytes()), &mut output)
+            .unwrap();
+        let output = String::from_utf8(output).unwrap();
+        assert!(output.starts_with("pomidor> pomidor is"));
+        assert!(output.contains("\npomidor>         ... Token { token_type: Spec(Lparen)"));
+        assert!(output.ends_with("\npomidor> "));
+
+        assert_eq!("λ >> ", Repl::new().prompt);
+        assert_eq!("    ... ", Repl::new().continuation_prompt);
+        assert_eq!("...", Repl::with_prompt("").continuation_prompt);
     }
 }
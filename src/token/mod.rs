@@ -1,13 +1,68 @@
+use crate::lexer::Lexer;
 use crate::token::Keyword::*;
 use crate::token::Literal::*;
 use crate::token::Spec::*;
+#[cfg(feature = "regex-matcher")]
 use regex::Regex;
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+use std::convert::TryFrom;
+use std::fmt;
+use std::ops::{Index, IndexMut, Range};
+#[cfg(feature = "regex-matcher")]
+use std::sync::OnceLock;
+use unicode_xid::UnicodeXID;
 
-const SPEC: &str = "=+-!*/<>,;(){}";
+// There are no bitwise operators, so a lone `&` or `|` lexes as `Illegal`.
+const SPEC_PATTERNS: [(&str, Spec); 18] = [
+    ("==", Equal),
+    ("!=", NotEqual),
+    ("?.", QuestionDot),
+    ("??", QuestionQuestion),
+    ("|>", Pipe),
+    ("?:", Elvis),
+    ("<=", Lte),
+    (">=", Gte),
+    ("&&", And),
+    ("||", Or),
+    ("+=", PlusAssign),
+    ("-=", MinusAssign),
+    ("*=", AsteriskAssign),
+    ("/=", SlashAssign),
+    ("**", StarStar),
+    ("::", PathSep),
+    ("->", Arrow),
+    ("..", DotDot),
+];
 
-const SPEC_PATTERNS: [&str; 2] = ["==", "!="];
+fn match_spec_char(ch: char) -> Option<(Spec, usize)> {
+    match ch {
+        '=' => Some((Assign, 1)),
+        '+' => Some((Plus, 1)),
+        '-' => Some((Minus, 1)),
+        '!' => Some((Bang, 1)),
+        '*' => Some((Asterisk, 1)),
+        '/' => Some((Slash, 1)),
+        '<' => Some((Lt, 1)),
+        '>' => Some((Gt, 1)),
+        ',' => Some((Comma, 1)),
+        ';' => Some((Semicolon, 1)),
+        '(' => Some((Lparen, 1)),
+        ')' => Some((Rparen, 1)),
+        '{' => Some((Lbrace, 1)),
+        '}' => Some((Rbrace, 1)),
+        '?' => Some((QuestionMark, 1)),
+        '@' => Some((At, 1)),
+        '%' => Some((Percent, 1)),
+        '[' => Some((Lbracket, 1)),
+        ']' => Some((Rbracket, 1)),
+        ':' => Some((Colon, 1)),
+        _ => None,
+    }
+}
 
 #[derive(Debug, Clone, Copy, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub enum Spec {
     Assign,
     Plus,
@@ -23,21 +78,74 @@ pub enum Spec {
     Rparen,
     Lbrace,
     Rbrace,
+    QuestionMark,
+    At,
     Equal,
     NotEqual,
+    QuestionDot,
+    QuestionQuestion,
+    Pipe,
+    Elvis,
+    Lte,
+    Gte,
+    And,
+    Or,
+    PlusAssign,
+    MinusAssign,
+    AsteriskAssign,
+    SlashAssign,
+    Percent,
+    StarStar,
+    Lbracket,
+    Rbracket,
+    Colon,
+    PathSep,
+    Arrow,
+    DotDot,
 }
 
-const TOKEN_REGEXP: [&str; 2] = ["^[A-Za-z]\\w*", "^\\d+"];
+pub type LiteralMatcher = fn(&str, usize) -> Option<TokenPos>;
+
+#[cfg(feature = "regex-matcher")]
+const TOKEN_REGEXP: [&str; 6] = [
+    "^[_\\p{XID_Start}]\\p{XID_Continue}*",
+    "^\\d+(_+\\d+)*\\.\\d+([eE][+-]?\\d+)?",
+    "^0[xX][0-9A-Fa-f]+",
+    "^0[bB][01]+",
+    "^0[oO][0-7]+",
+    "^\\d+(_+\\d+)*",
+];
+
+// A radix prefix followed by a decimal digit outside its base (`0b2`, `0o8`) is lexed
+// as an empty `Bin("0b")` or `Oct("0o")`, and the digits after it as `Illegal`. Any
+// other prefix without a digit of its base (`0x`, `0b`, `0og`) is `Illegal` covering
+// just the prefix; lexing resumes right after it.
+const RADIX_PREFIXES: [&str; 6] = ["0x", "0X", "0b", "0B", "0o", "0O"];
+
+/// How many bytes in front of a token the literal matchers may look at; text this
+/// close before a token can change how the token is lexed.
+pub const LOOK_BEHIND: usize = 2;
 
 #[derive(Debug, Clone, Copy, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub enum Literal {
     Ident,
+    Float,
+    Hex,
+    Bin,
+    Oct,
     Int,
+    Str,
+    Char,
 }
 
-const KEYWORDS: [&str; 7] = ["fn", "let", "true", "false", "if", "else", "return"];
+const KEYWORDS: [&str; 20] = [
+    "fn", "let", "true", "false", "if", "else", "return", "struct", "assert", "type", "while",
+    "for", "in", "break", "continue", "null", "const", "match", "import", "mod",
+];
 
 #[derive(Debug, Clone, Copy, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub enum Keyword {
     Function,
     Let,
@@ -46,135 +154,600 @@ pub enum Keyword {
     If,
     Else,
     Return,
+    Struct,
+    Assert,
+    Type,
+    While,
+    For,
+    In,
+    Break,
+    Continue,
+    Null,
+    Const,
+    Match,
+    Import,
+    Mod,
 }
 
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct UnknownKeyword(pub String);
+
 #[derive(Debug, Clone, Copy, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub enum TokenType {
     Spec(Spec),
     Literal(Literal),
     Keyword(Keyword),
     Illegal,
+    Eof,
+}
+
+#[derive(Debug, Clone, Copy, Default, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct SourceSpan {
+    pub source_id: u32,
+    pub start: usize,
+    pub end: usize,
+}
+
+impl SourceSpan {
+    pub const fn new(source_id: u32, start: usize, end: usize) -> SourceSpan {
+        SourceSpan {
+            source_id,
+            start,
+            end,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.end - self.start
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.start == self.end
+    }
 }
 
 #[derive(Debug, Clone, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct Token {
     pub token_type: TokenType,
     pub literal: Option<String>,
     pub line: usize,
+    pub col: usize,
+    pub span: SourceSpan,
 }
 
 impl Token {
-    pub const fn new(token_type: TokenType, literal: Option<String>, line: usize) -> Token {
+    pub const fn new(
+        token_type: TokenType,
+        literal: Option<String>,
+        line: usize,
+        col: usize,
+        span: SourceSpan,
+    ) -> Token {
         Token {
             token_type,
             literal,
             line,
+            col,
+            span,
+        }
+    }
+
+    pub fn source_id(&self) -> u32 {
+        self.span.source_id
+    }
+
+    pub fn try_parse_int(&self) -> Option<i64> {
+        match self.token_type {
+            TokenType::Literal(Int) => self.literal.as_deref()?.replace('_', "").parse().ok(),
+            _ => None,
+        }
+    }
+
+    /// Returns `None` for literals too large to be finite, such as `1.0e999`.
+    pub fn try_parse_float(&self) -> Option<f64> {
+        match self.token_type {
+            TokenType::Literal(Float) => {
+                let value: f64 = self.literal.as_deref()?.replace('_', "").parse().ok()?;
+                Some(value).filter(|value| value.is_finite())
+            }
+            _ => None,
+        }
+    }
+
+    pub fn is_keyword(&self) -> bool {
+        matches!(self.token_type, TokenType::Keyword(_))
+    }
+
+    pub fn is_literal(&self) -> bool {
+        matches!(self.token_type, TokenType::Literal(_))
+    }
+
+    pub fn is_spec(&self) -> bool {
+        matches!(self.token_type, TokenType::Spec(_))
+    }
+
+    pub fn is_keyword_of(&self, kw: Keyword) -> bool {
+        matches!(self.token_type, TokenType::Keyword(it) if it == kw)
+    }
+
+    pub fn is_spec_of(&self, s: Spec) -> bool {
+        matches!(self.token_type, TokenType::Spec(it) if it == s)
+    }
+
+    pub fn is_literal_of(&self, lit: Literal) -> bool {
+        matches!(self.token_type, TokenType::Literal(it) if it == lit)
+    }
+
+    pub fn as_keyword(&self) -> Option<Keyword> {
+        match self.token_type {
+            TokenType::Keyword(kw) => Some(kw),
+            _ => None,
+        }
+    }
+
+    pub fn as_spec(&self) -> Option<Spec> {
+        match self.token_type {
+            TokenType::Spec(s) => Some(s),
+            _ => None,
+        }
+    }
+
+    pub fn as_literal(&self) -> Option<Literal> {
+        match self.token_type {
+            TokenType::Literal(lit) => Some(lit),
+            _ => None,
+        }
+    }
+
+    pub fn into_literal_string(self) -> Option<String> {
+        self.literal
+    }
+}
+
+pub fn parse_int_radix(literal: &str) -> Option<i64> {
+    let (radix, digits) = match literal.get(..2) {
+        Some("0x") | Some("0X") => (16, &literal[2..]),
+        Some("0o") | Some("0O") => (8, &literal[2..]),
+        Some("0b") | Some("0B") => (2, &literal[2..]),
+        _ => (10, literal),
+    };
+    if digits.starts_with(|ch: char| !ch.is_ascii_alphanumeric()) {
+        return None;
+    }
+    i64::from_str_radix(digits, radix).ok()
+}
+
+/// Matches `_` or an XID_Start character followed by XID_Continue characters as
+/// `Literal(Ident)`; keywords are not resolved.
+pub fn match_ident(input: &str, start: usize) -> Option<TokenPos> {
+    let rest = &input[start..];
+    let mut chars = rest.char_indices();
+    match chars.next() {
+        Some((_, ch)) if ch == '_' || ch.is_xid_start() => {}
+        _ => return None,
+    }
+    let len = chars
+        .find(|(_, ch)| !ch.is_xid_continue())
+        .map_or(rest.len(), |(i, _)| i);
+    Some(TokenPos::new(TokenType::Literal(Ident), start, start + len))
+}
+
+/// Matches a digit followed by digits and `_` separators as `Literal(Int)`; trailing
+/// underscores are not part of the literal.
+pub fn match_int(input: &str, start: usize) -> Option<TokenPos> {
+    let bytes = input.as_bytes();
+    if !bytes.get(start)?.is_ascii_digit() {
+        return None;
+    }
+    let len = count_bytes(bytes, start, |b| b.is_ascii_digit() || b == b'_');
+    let len = input[start..start + len].trim_end_matches('_').len();
+    Some(TokenPos::new(TokenType::Literal(Int), start, start + len))
+}
+
+// A separator may not end a number: in `100_` the underscores right after the digits
+// are `Illegal` rather than the start of an identifier.
+fn match_trailing_separator(input: &str, start: usize) -> Option<TokenPos> {
+    let bytes = input.as_bytes();
+    if start == 0 || bytes.get(start) != Some(&b'_') || !bytes[start - 1].is_ascii_digit() {
+        return None;
+    }
+    let len = count_bytes(bytes, start, |b| b == b'_');
+    Some(TokenPos::new(TokenType::Illegal, start, start + len))
+}
+
+// The digits in `0b2` or `0o89` right after an empty binary or octal literal.
+fn match_digits_out_of_range(input: &str, start: usize) -> Option<TokenPos> {
+    let bytes = input.as_bytes();
+    if start < 2 || !bytes.get(start)?.is_ascii_digit() {
+        return None;
+    }
+    match input.get(start - 2..start)? {
+        "0b" | "0B" | "0o" | "0O" => {
+            let len = count_bytes(bytes, start, |b| b.is_ascii_digit());
+            Some(TokenPos::new(TokenType::Illegal, start, start + len))
         }
+        _ => None,
     }
 }
 
+// A radix prefix at `start` without a single digit of its base.
+fn match_empty_radix(input: &str, start: usize, prefix: &str) -> TokenPos {
+    let end = start + prefix.len();
+    let token_type = match (prefix.as_bytes()[1], input.as_bytes().get(end)) {
+        (b'b' | b'B', Some(b)) if b.is_ascii_digit() => TokenType::Literal(Bin),
+        (b'o' | b'O', Some(b)) if b.is_ascii_digit() => TokenType::Literal(Oct),
+        _ => TokenType::Illegal,
+    };
+    TokenPos::new(token_type, start, end)
+}
+
+// Separators come one at a time: `1__000` is a single `Illegal` token, while `_100`
+// is an identifier because it does not start with a digit.
+fn reject_double_separator(input: &str, pos: TokenPos) -> TokenPos {
+    match pos.token_type {
+        TokenType::Literal(Int) | TokenType::Literal(Float)
+            if input[pos.start..pos.end].contains("__") =>
+        {
+            TokenPos::new(TokenType::Illegal, pos.start, pos.end)
+        }
+        _ => pos,
+    }
+}
+
+fn count_bytes(bytes: &[u8], start: usize, pred: impl Fn(u8) -> bool) -> usize {
+    bytes
+        .get(start..)
+        .map_or(0, |rest| rest.iter().take_while(|b| pred(**b)).count())
+}
+
 #[derive(Debug, Clone, Copy, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct TokenPos {
     pub token_type: TokenType,
+    pub start: usize,
     pub end: usize,
 }
 
 impl TokenPos {
-    pub fn new(token_type: TokenType, end: usize) -> TokenPos {
-        TokenPos { token_type, end }
+    pub fn new(token_type: TokenType, start: usize, end: usize) -> TokenPos {
+        TokenPos {
+            token_type,
+            start,
+            end,
+        }
     }
 
-    pub fn token(&self, literal: Option<&str>, line: usize) -> Token {
+    pub fn token(&self, literal: Option<&str>, line: usize, col: usize, source_id: u32) -> Token {
         let token_type = self.token_type;
         Token {
             token_type,
             literal: literal.map(|s| s.to_string()),
             line,
+            col,
+            span: SourceSpan::new(source_id, self.start, self.end),
         }
     }
 }
 
+#[derive(Debug, Clone, Default, Eq, PartialEq)]
+pub struct TokenStream {
+    tokens: Vec<Token>,
+}
+
+impl TokenStream {
+    pub const fn new(tokens: Vec<Token>) -> TokenStream {
+        TokenStream { tokens }
+    }
+
+    pub fn tokens(&self) -> &[Token] {
+        &self.tokens
+    }
+
+    pub fn len(&self) -> usize {
+        self.tokens.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.tokens.is_empty()
+    }
+
+    pub fn to_source_string(&self) -> String {
+        self.tokens
+            .iter()
+            .filter_map(|token| source_text(token.token_type, token.literal.as_deref()))
+            .collect::<Vec<String>>()
+            .join(" ")
+    }
+}
+
+fn source_text(token_type: TokenType, literal: Option<&str>) -> Option<String> {
+    match (token_type, literal) {
+        (TokenType::Literal(Str), Some(literal)) => Some(quote(literal, '"')),
+        (TokenType::Literal(Char), Some(literal)) => Some(quote(literal, '\'')),
+        (_, Some(literal)) => Some(literal.to_string()),
+        (_, None) => token_type.as_str().map(|s| s.to_string()),
+    }
+}
+
+fn quote(literal: &str, delimiter: char) -> String {
+    let mut text = String::from(delimiter);
+    for ch in literal.chars() {
+        match ch {
+            '\\' => text.push_str("\\\\"),
+            '\n' => text.push_str("\\n"),
+            '\t' => text.push_str("\\t"),
+            '\r' => text.push_str("\\r"),
+            _ if ch == delimiter => {
+                text.push('\\');
+                text.push(ch);
+            }
+            _ => text.push(ch),
+        }
+    }
+    text.push(delimiter);
+    text
+}
+
+impl Index<usize> for TokenStream {
+    type Output = Token;
+
+    fn index(&self, index: usize) -> &Token {
+        &self.tokens[index]
+    }
+}
+
+impl IndexMut<usize> for TokenStream {
+    fn index_mut(&mut self, index: usize) -> &mut Token {
+        &mut self.tokens[index]
+    }
+}
+
+impl Index<Range<usize>> for TokenStream {
+    type Output = [Token];
+
+    fn index(&self, index: Range<usize>) -> &[Token] {
+        &self.tokens[index]
+    }
+}
+
+#[derive(Debug, Clone, Default, Eq, PartialEq)]
+pub struct TokenStreamBuilder {
+    tokens: Vec<Token>,
+}
+
+impl TokenStreamBuilder {
+    pub const fn new() -> TokenStreamBuilder {
+        TokenStreamBuilder { tokens: Vec::new() }
+    }
+
+    pub fn from_source<M>(source: &str, lexer: &Lexer<M>) -> TokenStreamBuilder
+    where
+        M: Fn(&str, usize) -> Option<TokenPos>,
+    {
+        TokenStreamBuilder {
+            tokens: lexer.tokenize_lossy(source).collect(),
+        }
+    }
+
+    pub fn push_token(&mut self, tt: TokenType) -> &mut Self {
+        let len = source_text(tt, None).map_or(0, |s| s.len());
+        let span = self.next_span(len);
+        self.tokens.push(Token::new(tt, None, 0, span.start, span));
+        self
+    }
+
+    pub fn push_literal(&mut self, tt: TokenType, literal: impl Into<String>) -> &mut Self {
+        let literal = literal.into();
+        let len = source_text(tt, Some(&literal)).map_or(0, |s| s.len());
+        let span = self.next_span(len);
+        self.tokens
+            .push(Token::new(tt, Some(literal), 0, span.start, span));
+        self
+    }
+
+    /// Places the next token one space after the previous one,
+    /// the same way `TokenStream::to_source_string` lays them out.
+    fn next_span(&self, len: usize) -> SourceSpan {
+        let start = self.tokens.last().map_or(0, |t| t.span.end + 1);
+        SourceSpan::new(0, start, start + len)
+    }
+
+    pub fn build(self) -> TokenStream {
+        TokenStream::new(self.tokens)
+    }
+}
+
 impl TokenType {
+    pub fn as_str(&self) -> Option<&'static str> {
+        match self {
+            TokenType::Spec(spec) => Some(spec.as_str()),
+            TokenType::Keyword(keyword) => Some(keyword.as_str()),
+            _ => None,
+        }
+    }
+
     pub fn match_spec(input: &str, start: usize, ch: char) -> Option<TokenPos> {
-        if let Some(i) = SPEC.find(ch) {
-            for (j, p) in SPEC_PATTERNS.iter().enumerate() {
-                if input[start..].starts_with(p) {
-                    return Spec::from_int(SPEC.len() + j)
-                        .map(|it| TokenPos::new(TokenType::Spec(it), start + p.len()));
-                }
+        for (p, spec) in SPEC_PATTERNS.iter() {
+            if input[start..].starts_with(p) {
+                return Some(TokenPos::new(
+                    TokenType::Spec(*spec),
+                    start,
+                    start + p.len(),
+                ));
             }
-            return Spec::from_int(i)
-                .map(|it| TokenPos::new(TokenType::Spec(it), start + ch.len_utf8()));
         }
-        None
+        match_spec_char(ch)
+            .map(|(spec, len)| TokenPos::new(TokenType::Spec(spec), start, start + len))
+    }
+
+    pub fn literal_token_matcher() -> LiteralMatcher {
+        TokenType::match_literal
     }
 
-    pub fn literal_token_matcher() -> Box<dyn Fn(&str, usize) -> Option<TokenPos>> {
-        let regexps: Vec<Regex> = TOKEN_REGEXP
+    pub fn match_literal(input: &str, start: usize) -> Option<TokenPos> {
+        TokenType::scan_literal(input, start).map(|pos| reject_double_separator(input, pos))
+    }
+
+    #[cfg(not(feature = "regex-matcher"))]
+    fn scan_literal(input: &str, start: usize) -> Option<TokenPos> {
+        if let Some(pos) = match_trailing_separator(input, start) {
+            return Some(pos);
+        }
+        if let Some(pos) = match_digits_out_of_range(input, start) {
+            return Some(pos);
+        }
+        if let Some(pos) = match_ident(input, start) {
+            let word = &input[pos.start..pos.end];
+            return match KEYWORDS.iter().position(|kw| kw.eq(&word)) {
+                Some(j) => Keyword::from_int(j)
+                    .map(|it| TokenPos::new(TokenType::Keyword(it), pos.start, pos.end)),
+                None => Some(pos),
+            };
+        }
+        let int = match_int(input, start)?;
+        let bytes = input.as_bytes();
+        if let Some(p) = RADIX_PREFIXES
             .iter()
-            .map(|pattern| Regex::new(pattern).unwrap())
-            .collect();
-        let keywords: Vec<String> = KEYWORDS.iter().map(|s| s.to_string()).collect();
-        Box::new(move |input: &str, start: usize| {
-            for (i, r) in regexps.iter().enumerate() {
-                if let Some(m) = r.find(&input[start..]) {
-                    for (j, kw) in keywords.iter().enumerate() {
-                        let end = start + m.end();
-                        if kw.eq(&input[start..end]) {
-                            return Keyword::from_int(j)
-                                .map(|it| TokenPos::new(TokenType::Keyword(it), end));
-                        }
+            .find(|p| input[start..].starts_with(*p))
+        {
+            let (literal, radix) = match p.as_bytes()[1] {
+                b'x' | b'X' => (Hex, 16),
+                b'b' | b'B' => (Bin, 2),
+                _ => (Oct, 8),
+            };
+            let digits = count_bytes(bytes, start + p.len(), |b| (b as char).is_digit(radix));
+            return Some(match digits {
+                0 => match_empty_radix(input, start, p),
+                n => TokenPos::new(TokenType::Literal(literal), start, start + p.len() + n),
+            });
+        }
+        let fraction = match bytes.get(int.end) {
+            Some(b'.') => count_bytes(bytes, int.end + 1, |b| b.is_ascii_digit()),
+            _ => 0,
+        };
+        if fraction == 0 {
+            return Some(int);
+        }
+        let mut end = int.end + 1 + fraction;
+        if let Some(b'e') | Some(b'E') = bytes.get(end) {
+            let sign = matches!(bytes.get(end + 1), Some(b'+') | Some(b'-')) as usize;
+            let exponent = count_bytes(bytes, end + 1 + sign, |b| b.is_ascii_digit());
+            if exponent > 0 {
+                end += 1 + sign + exponent;
+            }
+        }
+        Some(TokenPos::new(TokenType::Literal(Float), start, end))
+    }
+
+    #[cfg(feature = "regex-matcher")]
+    fn scan_literal(input: &str, start: usize) -> Option<TokenPos> {
+        if let Some(pos) = match_trailing_separator(input, start) {
+            return Some(pos);
+        }
+        if let Some(pos) = match_digits_out_of_range(input, start) {
+            return Some(pos);
+        }
+        static REGEXPS: OnceLock<Vec<Regex>> = OnceLock::new();
+        let regexps = REGEXPS.get_or_init(|| {
+            TOKEN_REGEXP
+                .iter()
+                .map(|pattern| Regex::new(pattern).unwrap())
+                .collect()
+        });
+        for (i, r) in regexps.iter().enumerate() {
+            if let Some(m) = r.find(&input[start..]) {
+                for (j, kw) in KEYWORDS.iter().enumerate() {
+                    let end = start + m.end();
+                    if kw.eq(&&input[start..end]) {
+                        return Keyword::from_int(j)
+                            .map(|it| TokenPos::new(TokenType::Keyword(it), start, end));
+                    }
+                }
+                let literal = Literal::from_int(i);
+                if literal == Some(Int) {
+                    if let Some(p) = RADIX_PREFIXES
+                        .iter()
+                        .find(|p| input[start..].starts_with(*p))
+                    {
+                        return Some(match_empty_radix(input, start, p));
                     }
-                    return Literal::from_int(i)
-                        .map(|it| TokenPos::new(TokenType::Literal(it), start + m.end()));
                 }
+                return literal
+                    .map(|it| TokenPos::new(TokenType::Literal(it), start, start + m.end()));
             }
-            None
-        })
+        }
+        None
     }
 }
 
-#[cfg(test)]
-mod test {
-    #[test]
-    fn test() {}
-}
-
 impl Spec {
-    fn from_int(i: usize) -> Option<Spec> {
-        match i {
-            0 => Some(Assign),
-            1 => Some(Plus),
-            2 => Some(Minus),
-            3 => Some(Bang),
-            4 => Some(Asterisk),
-            5 => Some(Slash),
-            6 => Some(Lt),
-            7 => Some(Gt),
-            8 => Some(Comma),
-            9 => Some(Semicolon),
-            10 => Some(Lparen),
-            11 => Some(Rparen),
-            12 => Some(Lbrace),
-            13 => Some(Rbrace),
-            14 => Some(Equal),
-            15 => Some(NotEqual),
-            _ => None,
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Assign => "=",
+            Plus => "+",
+            Minus => "-",
+            Bang => "!",
+            Asterisk => "*",
+            Slash => "/",
+            Lt => "<",
+            Gt => ">",
+            Comma => ",",
+            Semicolon => ";",
+            Lparen => "(",
+            Rparen => ")",
+            Lbrace => "{",
+            Rbrace => "}",
+            QuestionMark => "?",
+            At => "@",
+            Equal => "==",
+            NotEqual => "!=",
+            QuestionDot => "?.",
+            QuestionQuestion => "??",
+            Pipe => "|>",
+            Elvis => "?:",
+            Lte => "<=",
+            Gte => ">=",
+            And => "&&",
+            Or => "||",
+            PlusAssign => "+=",
+            MinusAssign => "-=",
+            AsteriskAssign => "*=",
+            SlashAssign => "/=",
+            Percent => "%",
+            StarStar => "**",
+            Lbracket => "[",
+            Rbracket => "]",
+            Colon => ":",
+            PathSep => "::",
+            Arrow => "->",
+            DotDot => "..",
         }
     }
 }
 
 impl Literal {
+    #[cfg(feature = "regex-matcher")]
     fn from_int(i: usize) -> Option<Literal> {
         match i {
             0 => Some(Ident),
-            1 => Some(Int),
+            1 => Some(Float),
+            2 => Some(Hex),
+            3 => Some(Bin),
+            4 => Some(Oct),
+            5 => Some(Int),
             _ => None,
         }
     }
 }
 
 impl Keyword {
+    pub fn as_str(&self) -> &'static str {
+        KEYWORDS[*self as usize]
+    }
+
     fn from_int(i: usize) -> Option<Keyword> {
         match i {
             0 => Some(Function),
@@ -184,7 +757,486 @@ impl Keyword {
             4 => Some(If),
             5 => Some(Else),
             6 => Some(Return),
+            7 => Some(Struct),
+            8 => Some(Assert),
+            9 => Some(Type),
+            10 => Some(While),
+            11 => Some(For),
+            12 => Some(In),
+            13 => Some(Break),
+            14 => Some(Continue),
+            15 => Some(Null),
+            16 => Some(Const),
+            17 => Some(Match),
+            18 => Some(Import),
+            19 => Some(Mod),
             _ => None,
         }
     }
 }
+
+impl TryFrom<&str> for Keyword {
+    type Error = UnknownKeyword;
+
+    fn try_from(value: &str) -> Result<Self, Self::Error> {
+        KEYWORDS
+            .iter()
+            .position(|kw| kw.eq(&value))
+            .and_then(Keyword::from_int)
+            .ok_or_else(|| UnknownKeyword(value.to_string()))
+    }
+}
+
+/// Formats the canonical source form.
+///
+/// ```
+/// use pomidor::token::Spec::*;
+///
+/// let specs = [
+///     (Assign, "="), (Plus, "+"), (Minus, "-"), (Bang, "!"), (Asterisk, "*"),
+///     (Slash, "/"), (Lt, "<"), (Gt, ">"), (Comma, ","), (Semicolon, ";"),
+///     (Lparen, "("), (Rparen, ")"), (Lbrace, "{"), (Rbrace, "}"),
+///     (QuestionMark, "?"), (At, "@"), (Equal, "=="), (NotEqual, "!="),
+///     (QuestionDot, "?."), (QuestionQuestion, "??"), (Pipe, "|>"), (Elvis, "?:"),
+///     (Lte, "<="), (Gte, ">="),
+///     (And, "&&"), (Or, "||"),
+///     (PlusAssign, "+="), (MinusAssign, "-="), (AsteriskAssign, "*="), (SlashAssign, "/="),
+///     (Percent, "%"), (StarStar, "**"),
+///     (Lbracket, "["), (Rbracket, "]"),
+///     (Colon, ":"), (PathSep, "::"),
+///     (Arrow, "->"),
+///     (DotDot, ".."),
+/// ];
+/// for (spec, text) in specs.iter() {
+///     assert_eq!(*text, spec.to_string());
+/// }
+/// ```
+impl fmt::Display for Spec {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// Formats the keyword as it is written in source.
+///
+/// ```
+/// use pomidor::token::Keyword::*;
+///
+/// let keywords = [
+///     (Function, "fn"), (Let, "let"), (True, "true"), (False, "false"), (If, "if"),
+///     (Else, "else"), (Return, "return"), (Struct, "struct"), (Assert, "assert"),
+///     (Type, "type"), (While, "while"), (For, "for"), (In, "in"), (Break, "break"),
+///     (Continue, "continue"), (Null, "null"), (Const, "const"), (Match, "match"),
+///     (Import, "import"), (Mod, "mod"),
+/// ];
+/// for (keyword, text) in keywords.iter() {
+///     assert_eq!(*text, keyword.to_string());
+/// }
+/// ```
+impl fmt::Display for Keyword {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// Formats the literal kind in angle brackets.
+///
+/// ```
+/// use pomidor::token::Literal::*;
+///
+/// let literals = [
+///     (Ident, "<ident>"), (Float, "<float>"), (Hex, "<hex>"), (Bin, "<bin>"),
+///     (Oct, "<oct>"), (Int, "<int>"), (Str, "<str>"), (Char, "<char>"),
+/// ];
+/// for (literal, text) in literals.iter() {
+///     assert_eq!(*text, literal.to_string());
+/// }
+/// ```
+impl fmt::Display for Literal {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Ident => "<ident>",
+            Float => "<float>",
+            Hex => "<hex>",
+            Bin => "<bin>",
+            Oct => "<oct>",
+            Int => "<int>",
+            Str => "<str>",
+            Char => "<char>",
+        })
+    }
+}
+
+impl fmt::Display for Token {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.literal {
+            Some(literal) => f.write_str(literal),
+            None => self.token_type.fmt(f),
+        }
+    }
+}
+
+/// Delegates to the inner `Spec`, `Literal` or `Keyword`.
+///
+/// ```
+/// use pomidor::token::{Keyword, Literal, Spec, TokenType};
+///
+/// assert_eq!("==", TokenType::Spec(Spec::Equal).to_string());
+/// assert_eq!("<int>", TokenType::Literal(Literal::Int).to_string());
+/// assert_eq!("return", TokenType::Keyword(Keyword::Return).to_string());
+/// assert_eq!("<illegal>", TokenType::Illegal.to_string());
+/// assert_eq!("<eof>", TokenType::Eof.to_string());
+/// ```
+impl fmt::Display for TokenType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TokenType::Spec(spec) => spec.fmt(f),
+            TokenType::Literal(literal) => literal.fmt(f),
+            TokenType::Keyword(keyword) => keyword.fmt(f),
+            TokenType::Illegal => f.write_str("<illegal>"),
+            TokenType::Eof => f.write_str("<eof>"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::lexer::Lexer;
+    use crate::token::Keyword::*;
+    use crate::token::Literal::*;
+    use crate::token::Spec::*;
+    use crate::token::TokenType::*;
+    use crate::token::{
+        match_ident, match_int, parse_int_radix, Keyword, SourceSpan, Token, TokenPos, TokenStream,
+        TokenStreamBuilder, TokenType, UnknownKeyword,
+    };
+    use std::convert::TryFrom;
+
+    #[test]
+    fn test() {}
+
+    #[test]
+    fn test_token_stream_builder() {
+        let mut builder = TokenStreamBuilder::new();
+        builder
+            .push_token(Keyword(Let))
+            .push_literal(Literal(Ident), "x")
+            .push_token(Spec(Assign))
+            .push_literal(Literal(Int), "5")
+            .push_token(Spec(NotEqual))
+            .push_literal(Literal(Int), "10")
+            .push_token(Spec(Semicolon))
+            .push_literal(Literal(Str), "say \"hi\"\n")
+            .push_literal(Literal(Char), "'");
+        let stream = builder.build();
+        assert_eq!(9, stream.len());
+
+        let source = stream.to_source_string();
+        assert_eq!("let x = 5 != 10 ; \"say \\\"hi\\\"\\n\" '\\''", source);
+
+        let lexer = Lexer::new();
+        let round_trip = TokenStreamBuilder::from_source(&source, &lexer).build();
+        assert_eq!(stream.tokens(), &round_trip[0..stream.len()]);
+        assert_eq!(Eof, round_trip[stream.len()].token_type);
+        assert_eq!(stream.len() + 1, round_trip.len());
+    }
+
+    #[test]
+    fn test_token_stream_index() {
+        let lexer = Lexer::new();
+        let mut stream = TokenStreamBuilder::from_source("let x = 5;", &lexer).build();
+        assert_eq!(Keyword(Let), stream[0].token_type);
+        assert_eq!(Some("5".to_string()), stream[3].literal);
+
+        let slice = &stream[1..3];
+        assert_eq!(2, slice.len());
+        assert_eq!(Literal(Ident), slice[0].token_type);
+        assert_eq!(Spec(Assign), slice[1].token_type);
+
+        stream[1].literal = Some("y".to_string());
+        assert_eq!("let y = 5 ;", stream.to_source_string());
+    }
+
+    #[test]
+    #[should_panic(expected = "index out of bounds")]
+    fn test_token_stream_index_out_of_bounds() {
+        let stream = TokenStream::new(Vec::new());
+        let _ = &stream[0];
+    }
+
+    #[test]
+    fn test_try_parse_int() {
+        let int = |s: &str| {
+            Token::new(
+                Literal(Int),
+                Some(s.to_string()),
+                0,
+                0,
+                SourceSpan::default(),
+            )
+        };
+        assert_eq!(Some(42), int("42").try_parse_int());
+        assert_eq!(Some(1_000_000), int("1_000_000").try_parse_int());
+        assert_eq!(Some(i64::MAX), int("9223372036854775807").try_parse_int());
+        assert_eq!(None, int("9223372036854775808").try_parse_int());
+        assert_eq!(None, int("0xFF").try_parse_int());
+
+        let ident = Token::new(
+            Literal(Ident),
+            Some("x".to_string()),
+            0,
+            0,
+            SourceSpan::default(),
+        );
+        assert_eq!(None, ident.try_parse_int());
+        let plus = Token::new(Spec(Plus), None, 0, 0, SourceSpan::default());
+        assert_eq!(None, plus.try_parse_int());
+    }
+
+    #[test]
+    fn test_try_parse_float() {
+        let float = |s: &str| {
+            Token::new(
+                Literal(Float),
+                Some(s.to_string()),
+                0,
+                0,
+                SourceSpan::default(),
+            )
+        };
+        assert_eq!(Some(1.5), float("1.5").try_parse_float());
+        assert_eq!(Some(1_000.5), float("1_000.5").try_parse_float());
+        assert_eq!(Some(1.5e10), float("1.5e10").try_parse_float());
+        assert_eq!(Some(1.5e-3), float("1.5e-3").try_parse_float());
+        assert_eq!(None, float("1e999").try_parse_float());
+        assert_eq!(None, float("1.5e999").try_parse_float());
+
+        let int = Token::new(
+            Literal(Int),
+            Some("15".to_string()),
+            0,
+            0,
+            SourceSpan::default(),
+        );
+        assert_eq!(None, int.try_parse_float());
+        let plus = Token::new(Spec(Plus), None, 0, 0, SourceSpan::default());
+        assert_eq!(None, plus.try_parse_float());
+    }
+
+    #[test]
+    fn test_parse_int_radix() {
+        assert_eq!(Some(255), parse_int_radix("0xFF"));
+        assert_eq!(Some(0xDEAD), parse_int_radix("0Xdead"));
+        assert_eq!(Some(0o755), parse_int_radix("0o755"));
+        assert_eq!(Some(42), parse_int_radix("0b101010"));
+        assert_eq!(Some(10), parse_int_radix("10"));
+        assert_eq!(None, parse_int_radix("0x"));
+        assert_eq!(None, parse_int_radix("0x-1"));
+        assert_eq!(None, parse_int_radix("0b102"));
+        assert_eq!(None, parse_int_radix("0x8000000000000000"));
+    }
+
+    #[test]
+    fn test_keyword_try_from() {
+        assert_eq!(Ok(Let), Keyword::try_from("let"));
+        assert_eq!(Ok(Function), Keyword::try_from("fn"));
+        assert_eq!(
+            Err(UnknownKeyword("lett".to_string())),
+            Keyword::try_from("lett")
+        );
+        assert_eq!(Err(UnknownKeyword("".to_string())), Keyword::try_from(""));
+
+        let keywords: Vec<Keyword> = (0..).map_while(Keyword::from_int).collect();
+        assert!(keywords.len() >= 7);
+        for keyword in keywords {
+            assert_eq!(Ok(keyword), Keyword::try_from(keyword.as_str()));
+        }
+    }
+
+    #[test]
+    fn test_match_spec_round_trip() {
+        let specs = [
+            Assign,
+            Plus,
+            Minus,
+            Bang,
+            Asterisk,
+            Slash,
+            Lt,
+            Gt,
+            Comma,
+            Semicolon,
+            Lparen,
+            Rparen,
+            Lbrace,
+            Rbrace,
+            QuestionMark,
+            At,
+            Equal,
+            NotEqual,
+            QuestionDot,
+            QuestionQuestion,
+            Pipe,
+            Elvis,
+            Lte,
+            Gte,
+            And,
+            Or,
+            PlusAssign,
+            MinusAssign,
+            AsteriskAssign,
+            SlashAssign,
+            Percent,
+            StarStar,
+            Lbracket,
+            Rbracket,
+            Colon,
+            PathSep,
+            Arrow,
+            DotDot,
+        ];
+        for spec in specs.iter() {
+            let text = spec.as_str();
+            let ch = text.chars().next().unwrap();
+            let pos = TokenType::match_spec(text, 0, ch).unwrap();
+            assert_eq!(Spec(*spec), pos.token_type);
+            assert_eq!(text.len(), pos.end);
+        }
+        assert!(TokenType::match_spec("#", 0, '#').is_none());
+    }
+
+    #[test]
+    fn test_match_ident() {
+        let ident = |start, end| Some(TokenPos::new(Literal(Ident), start, end));
+        assert_eq!(ident(0, 6), match_ident("foo_42 + 1", 0));
+        assert_eq!(ident(2, 4), match_ident("- _x", 2));
+        assert_eq!(ident(0, 4), match_ident("_100", 0));
+        assert_eq!(None, match_ident("42", 0));
+        assert_eq!(None, match_ident("x", 1));
+        assert_eq!(ident(1, 4), match_ident("(let)", 1));
+        assert_eq!(ident(0, 6), match_ident("αβγ = 1", 0));
+        assert_eq!(ident(0, 6), match_ident("变量", 0));
+        assert_eq!(ident(2, 13), match_ident("- x_变量2β", 2));
+        assert_eq!(ident(0, 2), match_ident("a1😀b", 0));
+        assert_eq!(None, match_ident("😀", 0));
+        assert_eq!(None, match_ident("٣", 0));
+    }
+
+    #[test]
+    fn test_match_int() {
+        let int = |start, end| Some(TokenPos::new(Literal(Int), start, end));
+        assert_eq!(int(0, 3), match_int("123abc", 0));
+        assert_eq!(int(1, 2), match_int("(0x1F)", 1));
+        assert_eq!(None, match_int("abc", 0));
+        assert_eq!(None, match_int("1", 1));
+        assert_eq!(int(0, 5), match_int("1_000", 0));
+        assert_eq!(int(0, 3), match_int("100_", 0));
+        assert_eq!(None, match_int("_100", 0));
+    }
+
+    #[test]
+    fn test_token_display() {
+        let lexer = Lexer::new();
+        let shown: Vec<String> = lexer
+            .tokenize_lossy("let x = 0x1F != \"a b\"; 💥")
+            .map(|t| t.to_string())
+            .collect();
+        assert_eq!(
+            vec![
+                "let",
+                "x",
+                "=",
+                "0x1F",
+                "!=",
+                "a b",
+                ";",
+                "<illegal>",
+                "<eof>"
+            ],
+            shown
+        );
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_serde_round_trip() {
+        let lexer = Lexer::new();
+        let tokens = lexer.tokenize_all("let s = \"a\\n\" ?? 1.5e3; 💥");
+        let json = serde_json::to_string(&tokens).unwrap();
+        assert_eq!(tokens, serde_json::from_str::<Vec<Token>>(&json).unwrap());
+
+        let token = Token::new(Spec(Elvis), None, 1, 2, SourceSpan::new(3, 4, 6));
+        let json = serde_json::to_string(&token).unwrap();
+        assert_eq!(
+            r#"{"token_type":{"Spec":"Elvis"},"literal":null,"line":1,"col":2,"span":{"source_id":3,"start":4,"end":6}}"#,
+            json
+        );
+        assert_eq!(token, serde_json::from_str(&json).unwrap());
+
+        let pos = TokenPos::new(Keyword(Return), 0, 6);
+        let json = serde_json::to_string(&pos).unwrap();
+        assert_eq!(pos, serde_json::from_str(&json).unwrap());
+    }
+
+    #[test]
+    fn test_token_predicates() {
+        let token = |tt| Token::new(tt, None, 0, 0, SourceSpan::default());
+        let kinds = |t: &Token| (t.is_keyword(), t.is_literal(), t.is_spec());
+
+        let keyword = token(Keyword(If));
+        assert_eq!((true, false, false), kinds(&keyword));
+        assert!(keyword.is_keyword_of(If));
+        assert!(!keyword.is_keyword_of(Else));
+        assert!(!keyword.is_spec_of(Plus));
+        assert!(!keyword.is_literal_of(Ident));
+
+        let literal = token(Literal(Float));
+        assert_eq!((false, true, false), kinds(&literal));
+        assert!(literal.is_literal_of(Float));
+        assert!(!literal.is_literal_of(Int));
+        assert!(!literal.is_keyword_of(If));
+        assert!(!literal.is_spec_of(Plus));
+
+        let spec = token(Spec(Plus));
+        assert_eq!((false, false, true), kinds(&spec));
+        assert!(spec.is_spec_of(Plus));
+        assert!(!spec.is_spec_of(Minus));
+        assert!(!spec.is_keyword_of(If));
+        assert!(!spec.is_literal_of(Ident));
+
+        for other in [token(Illegal), token(Eof)].iter() {
+            assert_eq!((false, false, false), kinds(other));
+            assert!(!other.is_keyword_of(If));
+            assert!(!other.is_spec_of(Plus));
+            assert!(!other.is_literal_of(Ident));
+        }
+    }
+
+    #[test]
+    fn test_token_accessors() {
+        let token = |tt| Token::new(tt, None, 0, 0, SourceSpan::default());
+        let parts = |t: &Token| (t.as_keyword(), t.as_spec(), t.as_literal());
+
+        assert_eq!((Some(Return), None, None), parts(&token(Keyword(Return))));
+        assert_eq!((None, Some(Pipe), None), parts(&token(Spec(Pipe))));
+        assert_eq!((None, None, Some(Hex)), parts(&token(Literal(Hex))));
+        assert_eq!((None, None, None), parts(&token(Illegal)));
+        assert_eq!((None, None, None), parts(&token(Eof)));
+        assert_eq!(Some(true), token(Spec(Plus)).as_spec().map(|s| s == Plus));
+
+        let lexer = Lexer::new();
+        let tokens = lexer.tokenize_all("x \"a b\" 7 ;");
+        let strings: Vec<Option<String>> =
+            tokens.into_iter().map(Token::into_literal_string).collect();
+        assert_eq!(
+            vec![
+                Some("x".to_string()),
+                Some("a b".to_string()),
+                Some("7".to_string()),
+                None,
+                None
+            ],
+            strings
+        );
+    }
+}